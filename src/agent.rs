@@ -0,0 +1,576 @@
+//! Conversion between `UserConfig` and the `user-config.json` document the
+//! UHK Agent reads and writes.
+
+use crate::{
+    config::{
+        Backlighting, DataModel, KeyAction, KeymapConfig, Keystroke, LayerConfig, Macro,
+        MacroAction, Modifiers, ModuleConfig, ModuleConfiguration, MouseConfig, Rgb, Scancode,
+        UserConfig,
+    },
+    device::Version,
+    names::{
+        self, find_value, layer_name, name_of, BACKLIGHTING_MODES, KEYSTROKE_TYPES,
+        MACRO_SUB_ACTIONS, MOUSE_ACTIONS, POINTER_FUNCTIONS, POINTER_MODES, SECONDARY_ROLES,
+        SWITCH_LAYER_MODES,
+    },
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("json error")]
+    Json(#[from] serde_json::Error),
+    #[error("unknown {0} {1:?}")]
+    UnknownName(&'static str, String),
+    #[error("no keymap with abbreviation {0:?}")]
+    UnknownKeymap(String),
+    #[error("keymap index {0} out of range")]
+    KeymapIndex(u8),
+    #[error("unsupported data model {0}")]
+    DataModel(Version),
+}
+
+pub type AgentResult<T> = Result<T, AgentError>;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentUserConfig {
+    pub user_config_major_version: u16,
+    pub user_config_minor_version: u16,
+    pub user_config_patch_version: u16,
+    pub device_name: String,
+    pub double_tap_switch_layer_timeout: u16,
+    pub icons_and_layer_texts_brightness: u8,
+    pub alphanumeric_segments_brightness: u8,
+    pub key_backlight_brightness: u8,
+    /// Only present from data model 6 on, as are the module axis locks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backlighting_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backlighting_color: Option<Rgb>,
+    pub mouse_move_initial_speed: u8,
+    pub mouse_move_acceleration: u8,
+    pub mouse_move_decelerated_speed: u8,
+    pub mouse_move_base_speed: u8,
+    pub mouse_move_accelerated_speed: u8,
+    pub mouse_scroll_initial_speed: u8,
+    pub mouse_scroll_acceleration: u8,
+    pub mouse_scroll_decelerated_speed: u8,
+    pub mouse_scroll_base_speed: u8,
+    pub mouse_scroll_accelerated_speed: u8,
+    pub module_configurations: Vec<AgentModuleConfiguration>,
+    pub keymaps: Vec<AgentKeymap>,
+    pub macros: Vec<AgentMacro>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModuleConfiguration {
+    pub id: u8,
+    pub pointer_mode: String,
+    pub decelerated_pointer_speed_multiplier: u8,
+    pub base_pointer_speed_multiplier: u8,
+    pub accelerated_pointer_speed_multiplier: u8,
+    pub angular_shift: u16,
+    pub mod_layer_pointer_function: String,
+    pub fn_layer_pointer_function: String,
+    pub mouse_layer_pointer_function: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_axis_lock: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scroll_axis_lock: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentKeymap {
+    pub is_default: bool,
+    pub abbreviation: String,
+    pub name: String,
+    pub description: String,
+    pub layers: Vec<AgentLayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentLayer {
+    /// Layer name; older documents omit it and rely on the layer position.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub modules: Vec<AgentModule>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModule {
+    pub id: u8,
+    pub key_actions: Vec<Option<AgentKeyAction>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "keyActionType", rename_all = "camelCase")]
+pub enum AgentKeyAction {
+    #[serde(rename_all = "camelCase")]
+    Keystroke {
+        #[serde(rename = "type")]
+        atype: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scancode: Option<u16>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        modifier_mask: Option<u8>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secondary_role_action: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    SwitchLayer {
+        layer: String,
+        switch_layer_mode: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SwitchKeymap { keymap_abbreviation: String },
+    #[serde(rename = "mouseAction", rename_all = "camelCase")]
+    Mouse { mouse_action: String },
+    #[serde(rename_all = "camelCase")]
+    PlayMacro { macro_index: u8 },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMacro {
+    pub is_looped: bool,
+    pub is_private: bool,
+    pub name: String,
+    pub macro_actions: Vec<AgentMacroAction>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "macroActionType", rename_all = "camelCase")]
+pub enum AgentMacroAction {
+    #[serde(rename_all = "camelCase")]
+    Key {
+        action: String,
+        #[serde(rename = "type")]
+        atype: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scancode: Option<u16>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        modifier_mask: Option<u8>,
+    },
+    #[serde(rename_all = "camelCase")]
+    MouseButton {
+        action: String,
+        mouse_buttons_mask: u8,
+    },
+    MoveMouse {
+        x: i16,
+        y: i16,
+    },
+    ScrollMouse {
+        x: i16,
+        y: i16,
+    },
+    Delay {
+        delay: u16,
+    },
+    Text {
+        text: String,
+    },
+    Command {
+        command: String,
+    },
+}
+
+fn value_of<T: Copy>(table: &[(T, &str)], what: &'static str, name: &str) -> AgentResult<T> {
+    find_value(table, name).ok_or_else(|| AgentError::UnknownName(what, name.to_string()))
+}
+
+fn layer_id(name: &str) -> AgentResult<u8> {
+    names::layer_id(name).ok_or_else(|| AgentError::UnknownName("layer", name.to_string()))
+}
+
+impl AgentUserConfig {
+    pub fn from_config(config: &UserConfig) -> AgentResult<Self> {
+        let mouse = &config.mouse_config;
+        let v6 = DataModel::of(config.version()) >= Some(DataModel::V6);
+        Ok(Self {
+            user_config_major_version: config.major,
+            user_config_minor_version: config.minor,
+            user_config_patch_version: config.patch,
+            device_name: config.name.clone(),
+            double_tap_switch_layer_timeout: config.double_tap_switch_layer_timeout,
+            icons_and_layer_texts_brightness: config.icons_and_layer_texts_brightness,
+            alphanumeric_segments_brightness: config.alphanumeric_segments_brighrness,
+            key_backlight_brightness: config.key_backlight_brightness,
+            backlighting_mode: v6
+                .then(|| name_of(BACKLIGHTING_MODES, config.backlighting.mode).to_string()),
+            backlighting_color: v6.then_some(config.backlighting.color),
+            mouse_move_initial_speed: mouse.move_initial_speed,
+            mouse_move_acceleration: mouse.move_acceleration,
+            mouse_move_decelerated_speed: mouse.move_decelerated_speed,
+            mouse_move_base_speed: mouse.move_base_speed,
+            mouse_move_accelerated_speed: mouse.move_accelerated_speed,
+            mouse_scroll_initial_speed: mouse.scroll_initial_speed,
+            mouse_scroll_acceleration: mouse.scroll_acceleration,
+            mouse_scroll_decelerated_speed: mouse.scroll_decelerated_speed,
+            mouse_scroll_base_speed: mouse.scroll_base_speed,
+            mouse_scroll_accelerated_speed: mouse.scroll_accelerated_speed,
+            module_configurations: config
+                .module_configurations
+                .iter()
+                .map(|module| AgentModuleConfiguration {
+                    id: module.id,
+                    pointer_mode: name_of(POINTER_MODES, module.pointer_mode).to_string(),
+                    decelerated_pointer_speed_multiplier: module
+                        .decelerated_pointer_speed_multiplier,
+                    base_pointer_speed_multiplier: module.base_pointer_speed_multiplier,
+                    accelerated_pointer_speed_multiplier: module
+                        .accelerated_pointer_speed_multiplier,
+                    angular_shift: module.angular_shift,
+                    mod_layer_pointer_function: name_of(
+                        POINTER_FUNCTIONS,
+                        module.mod_layer_pointer_function,
+                    )
+                    .to_string(),
+                    fn_layer_pointer_function: name_of(
+                        POINTER_FUNCTIONS,
+                        module.fn_layer_pointer_function,
+                    )
+                    .to_string(),
+                    mouse_layer_pointer_function: name_of(
+                        POINTER_FUNCTIONS,
+                        module.mouse_layer_pointer_function,
+                    )
+                    .to_string(),
+                    cursor_axis_lock: v6.then_some(module.cursor_axis_lock),
+                    scroll_axis_lock: v6.then_some(module.scroll_axis_lock),
+                })
+                .collect(),
+            keymaps: config
+                .keymaps
+                .iter()
+                .map(|keymap| export_keymap(config, keymap))
+                .try_collect()?,
+            macros: config.macros.iter().map(export_macro).collect(),
+        })
+    }
+
+    pub fn to_config(&self) -> AgentResult<UserConfig> {
+        let version = Version::new(
+            self.user_config_major_version,
+            self.user_config_minor_version,
+            self.user_config_patch_version,
+        );
+        if DataModel::of(version).is_none() {
+            return Err(AgentError::DataModel(version));
+        }
+        let default = Backlighting::default();
+        Ok(UserConfig {
+            major: self.user_config_major_version,
+            minor: self.user_config_minor_version,
+            patch: self.user_config_patch_version,
+            // filled in by UserConfig::serialize
+            length: 0,
+            name: self.device_name.clone(),
+            double_tap_switch_layer_timeout: self.double_tap_switch_layer_timeout,
+            icons_and_layer_texts_brightness: self.icons_and_layer_texts_brightness,
+            alphanumeric_segments_brighrness: self.alphanumeric_segments_brightness,
+            key_backlight_brightness: self.key_backlight_brightness,
+            backlighting: Backlighting {
+                mode: match &self.backlighting_mode {
+                    Some(mode) => value_of(BACKLIGHTING_MODES, "backlighting mode", mode)?,
+                    None => default.mode,
+                },
+                color: self.backlighting_color.unwrap_or(default.color),
+            },
+            mouse_config: MouseConfig {
+                move_initial_speed: self.mouse_move_initial_speed,
+                move_acceleration: self.mouse_move_acceleration,
+                move_decelerated_speed: self.mouse_move_decelerated_speed,
+                move_base_speed: self.mouse_move_base_speed,
+                move_accelerated_speed: self.mouse_move_accelerated_speed,
+                scroll_initial_speed: self.mouse_scroll_initial_speed,
+                scroll_acceleration: self.mouse_scroll_acceleration,
+                scroll_decelerated_speed: self.mouse_scroll_decelerated_speed,
+                scroll_base_speed: self.mouse_scroll_base_speed,
+                scroll_accelerated_speed: self.mouse_scroll_accelerated_speed,
+            },
+            module_configurations: self
+                .module_configurations
+                .iter()
+                .map(|module| {
+                    Ok(ModuleConfiguration {
+                        id: module.id,
+                        pointer_mode: value_of(
+                            POINTER_MODES,
+                            "pointer mode",
+                            &module.pointer_mode,
+                        )?,
+                        decelerated_pointer_speed_multiplier: module
+                            .decelerated_pointer_speed_multiplier,
+                        base_pointer_speed_multiplier: module.base_pointer_speed_multiplier,
+                        accelerated_pointer_speed_multiplier: module
+                            .accelerated_pointer_speed_multiplier,
+                        angular_shift: module.angular_shift,
+                        mod_layer_pointer_function: value_of(
+                            POINTER_FUNCTIONS,
+                            "pointer function",
+                            &module.mod_layer_pointer_function,
+                        )?,
+                        fn_layer_pointer_function: value_of(
+                            POINTER_FUNCTIONS,
+                            "pointer function",
+                            &module.fn_layer_pointer_function,
+                        )?,
+                        mouse_layer_pointer_function: value_of(
+                            POINTER_FUNCTIONS,
+                            "pointer function",
+                            &module.mouse_layer_pointer_function,
+                        )?,
+                        cursor_axis_lock: module.cursor_axis_lock.unwrap_or_default(),
+                        scroll_axis_lock: module.scroll_axis_lock.unwrap_or_default(),
+                    })
+                })
+                .collect::<AgentResult<_>>()?,
+            macros: self.macros.iter().map(import_macro).try_collect()?,
+            keymaps: self
+                .keymaps
+                .iter()
+                .map(|keymap| import_keymap(self, keymap))
+                .try_collect()?,
+        })
+    }
+}
+
+pub fn to_json(config: &UserConfig) -> AgentResult<String> {
+    Ok(serde_json::to_string_pretty(
+        &AgentUserConfig::from_config(config)?,
+    )?)
+}
+
+pub fn from_json(json: &str) -> AgentResult<UserConfig> {
+    serde_json::from_str::<AgentUserConfig>(json)?.to_config()
+}
+
+fn export_keymap(config: &UserConfig, keymap: &KeymapConfig) -> AgentResult<AgentKeymap> {
+    Ok(AgentKeymap {
+        is_default: keymap.default,
+        abbreviation: keymap.abbr.clone(),
+        name: keymap.name.clone(),
+        description: keymap.desc.clone(),
+        layers: keymap
+            .layers
+            .iter()
+            .map(|layer| {
+                Ok(AgentLayer {
+                    id: Some(layer_name(layer.id)),
+                    modules: layer
+                        .modules
+                        .iter()
+                        .map(|module| {
+                            Ok(AgentModule {
+                                id: module.id,
+                                key_actions: module
+                                    .key_actions
+                                    .iter()
+                                    .map(|action| export_key_action(config, action))
+                                    .try_collect()?,
+                            })
+                        })
+                        .collect::<AgentResult<_>>()?,
+                })
+            })
+            .collect::<AgentResult<_>>()?,
+    })
+}
+
+fn export_key_action(
+    config: &UserConfig,
+    action: &KeyAction,
+) -> AgentResult<Option<AgentKeyAction>> {
+    Ok(Some(match action {
+        KeyAction::None => return Ok(None),
+        KeyAction::Keystroke(keystroke) => AgentKeyAction::Keystroke {
+            atype: name_of(KEYSTROKE_TYPES, keystroke.atype).to_string(),
+            scancode: keystroke.scancode.map(|scancode| scancode.0),
+            modifier_mask: keystroke.modifiers.map(|modifiers| modifiers.bits()),
+            secondary_role_action: keystroke
+                .secondary_role
+                .map(|role| name_of(SECONDARY_ROLES, role).to_string()),
+        },
+        KeyAction::SwitchLayer(layer, mode) => AgentKeyAction::SwitchLayer {
+            layer: layer_name(*layer),
+            switch_layer_mode: name_of(SWITCH_LAYER_MODES, *mode).to_string(),
+        },
+        KeyAction::SwitchKeymap(index) => AgentKeyAction::SwitchKeymap {
+            keymap_abbreviation: config
+                .keymaps
+                .get(*index as usize)
+                .map(|keymap| keymap.abbr.clone())
+                .ok_or(AgentError::KeymapIndex(*index))?,
+        },
+        KeyAction::MouseAction(mouse) => AgentKeyAction::Mouse {
+            mouse_action: name_of(MOUSE_ACTIONS, *mouse).to_string(),
+        },
+        KeyAction::PlayMacroAction(index) => AgentKeyAction::PlayMacro {
+            macro_index: *index,
+        },
+    }))
+}
+
+fn export_macro(macro_: &Macro) -> AgentMacro {
+    AgentMacro {
+        is_looped: macro_.looped,
+        is_private: macro_.private,
+        name: macro_.name.clone(),
+        macro_actions: macro_
+            .actions
+            .iter()
+            .map(|action| match action {
+                MacroAction::Key {
+                    action,
+                    atype,
+                    scancode,
+                    modifiers,
+                } => AgentMacroAction::Key {
+                    action: name_of(MACRO_SUB_ACTIONS, *action).to_string(),
+                    atype: name_of(KEYSTROKE_TYPES, *atype).to_string(),
+                    scancode: scancode.map(|scancode| scancode.0),
+                    modifier_mask: modifiers.map(|modifiers| modifiers.bits()),
+                },
+                MacroAction::MouseButton { action, buttons } => AgentMacroAction::MouseButton {
+                    action: name_of(MACRO_SUB_ACTIONS, *action).to_string(),
+                    mouse_buttons_mask: *buttons,
+                },
+                MacroAction::MoveMouse { x, y } => AgentMacroAction::MoveMouse { x: *x, y: *y },
+                MacroAction::ScrollMouse { x, y } => AgentMacroAction::ScrollMouse { x: *x, y: *y },
+                MacroAction::Delay(delay) => AgentMacroAction::Delay { delay: *delay },
+                MacroAction::Text(text) => AgentMacroAction::Text { text: text.clone() },
+                MacroAction::Command(command) => AgentMacroAction::Command {
+                    command: command.clone(),
+                },
+            })
+            .collect(),
+    }
+}
+
+fn import_keymap(agent: &AgentUserConfig, keymap: &AgentKeymap) -> AgentResult<KeymapConfig> {
+    Ok(KeymapConfig {
+        abbr: keymap.abbreviation.clone(),
+        default: keymap.is_default,
+        name: keymap.name.clone(),
+        desc: keymap.description.clone(),
+        layers: keymap
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                Ok(LayerConfig {
+                    id: match &layer.id {
+                        Some(name) => layer_id(name)?,
+                        None => i as u8,
+                    },
+                    modules: layer
+                        .modules
+                        .iter()
+                        .map(|module| {
+                            Ok(ModuleConfig {
+                                id: module.id,
+                                key_actions: module
+                                    .key_actions
+                                    .iter()
+                                    .map(|action| import_key_action(agent, action.as_ref()))
+                                    .try_collect()?,
+                            })
+                        })
+                        .collect::<AgentResult<_>>()?,
+                })
+            })
+            .collect::<AgentResult<_>>()?,
+    })
+}
+
+fn import_key_action(
+    agent: &AgentUserConfig,
+    action: Option<&AgentKeyAction>,
+) -> AgentResult<KeyAction> {
+    Ok(match action {
+        None => KeyAction::None,
+        Some(AgentKeyAction::Keystroke {
+            atype,
+            scancode,
+            modifier_mask,
+            secondary_role_action,
+        }) => KeyAction::Keystroke(Keystroke {
+            atype: value_of(KEYSTROKE_TYPES, "keystroke type", atype)?,
+            scancode: scancode.map(Scancode),
+            modifiers: modifier_mask.map(Modifiers::from_bits_retain),
+            secondary_role: secondary_role_action
+                .as_deref()
+                .map(|role| value_of(SECONDARY_ROLES, "secondary role", role))
+                .transpose()?,
+        }),
+        Some(AgentKeyAction::SwitchLayer {
+            layer,
+            switch_layer_mode,
+        }) => KeyAction::SwitchLayer(
+            layer_id(layer)?,
+            value_of(SWITCH_LAYER_MODES, "switch layer mode", switch_layer_mode)?,
+        ),
+        Some(AgentKeyAction::SwitchKeymap {
+            keymap_abbreviation,
+        }) => KeyAction::SwitchKeymap(
+            agent
+                .keymaps
+                .iter()
+                .position(|keymap| keymap.abbreviation == *keymap_abbreviation)
+                .ok_or_else(|| AgentError::UnknownKeymap(keymap_abbreviation.clone()))?
+                as u8,
+        ),
+        Some(AgentKeyAction::Mouse { mouse_action }) => {
+            KeyAction::MouseAction(value_of(MOUSE_ACTIONS, "mouse action", mouse_action)?)
+        }
+        Some(AgentKeyAction::PlayMacro { macro_index }) => KeyAction::PlayMacroAction(*macro_index),
+    })
+}
+
+fn import_macro(macro_: &AgentMacro) -> AgentResult<Macro> {
+    Ok(Macro {
+        looped: macro_.is_looped,
+        private: macro_.is_private,
+        name: macro_.name.clone(),
+        actions: macro_
+            .macro_actions
+            .iter()
+            .map(|action| {
+                Ok(match action {
+                    AgentMacroAction::Key {
+                        action,
+                        atype,
+                        scancode,
+                        modifier_mask,
+                    } => MacroAction::Key {
+                        action: value_of(MACRO_SUB_ACTIONS, "macro action", action)?,
+                        atype: value_of(KEYSTROKE_TYPES, "keystroke type", atype)?,
+                        scancode: scancode.map(Scancode),
+                        modifiers: modifier_mask.map(Modifiers::from_bits_retain),
+                    },
+                    AgentMacroAction::MouseButton {
+                        action,
+                        mouse_buttons_mask,
+                    } => MacroAction::MouseButton {
+                        action: value_of(MACRO_SUB_ACTIONS, "macro action", action)?,
+                        buttons: *mouse_buttons_mask,
+                    },
+                    AgentMacroAction::MoveMouse { x, y } => MacroAction::MoveMouse { x: *x, y: *y },
+                    AgentMacroAction::ScrollMouse { x, y } => {
+                        MacroAction::ScrollMouse { x: *x, y: *y }
+                    }
+                    AgentMacroAction::Delay { delay } => MacroAction::Delay(*delay),
+                    AgentMacroAction::Text { text } => MacroAction::Text(text.clone()),
+                    AgentMacroAction::Command { command } => MacroAction::Command(command.clone()),
+                })
+            })
+            .collect::<AgentResult<_>>()?,
+    })
+}
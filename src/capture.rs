@@ -0,0 +1,160 @@
+use crate::{
+    device::{DeviceError, DeviceResult},
+    transport::Transport,
+};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Write as _,
+    io::{BufRead, Write},
+    time::{Duration, Instant},
+};
+
+const HEADER: &str = "# uhkctl capture v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Out,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub timestamp: Duration,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+impl Record {
+    /// Formats the record as `<micros> <'>'|'<'> <hex bytes>`.
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {}",
+            self.timestamp.as_micros(),
+            match self.direction {
+                Direction::Out => '>',
+                Direction::In => '<',
+            }
+        );
+        for byte in &self.data {
+            write!(line, " {:02x}", byte).unwrap();
+        }
+        line
+    }
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_ascii_whitespace();
+        let timestamp = Duration::from_micros(fields.next()?.parse().ok()?);
+        let direction = match fields.next()? {
+            ">" => Direction::Out,
+            "<" => Direction::In,
+            _ => return None,
+        };
+        let data = fields
+            .map(|byte| u8::from_str_radix(byte, 16).ok())
+            .collect::<Option<_>>()?;
+        Some(Self {
+            timestamp,
+            direction,
+            data,
+        })
+    }
+}
+
+pub fn read_capture(reader: impl BufRead) -> DeviceResult<Vec<Record>> {
+    let mut records = vec![];
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        records.push(Record::from_line(line).ok_or(DeviceError::Capture(n + 1))?);
+    }
+    Ok(records)
+}
+
+/// Transport wrapper logging every report and response to a capture file.
+pub struct Recorder<T: Transport, W: Write> {
+    inner: T,
+    out: RefCell<W>,
+    start: Instant,
+}
+
+impl<T: Transport, W: Write> Recorder<T, W> {
+    pub fn new(inner: T, mut out: W) -> DeviceResult<Self> {
+        writeln!(out, "{}", HEADER)?;
+        Ok(Self {
+            inner,
+            out: RefCell::new(out),
+            start: Instant::now(),
+        })
+    }
+    pub fn into_inner(self) -> (T, W) {
+        (self.inner, self.out.into_inner())
+    }
+    fn record(&self, direction: Direction, data: &[u8]) -> DeviceResult<()> {
+        let record = Record {
+            timestamp: self.start.elapsed(),
+            direction,
+            data: data.to_vec(),
+        };
+        let mut out = self.out.borrow_mut();
+        writeln!(out, "{}", record.to_line())?;
+        // keep the capture usable even if uhkctl dies mid-session
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: Transport, W: Write> Transport for Recorder<T, W> {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        self.record(Direction::Out, report)?;
+        self.inner.send(report)
+    }
+    fn receive(&self, timeout: Duration) -> DeviceResult<Vec<u8>> {
+        let response = self.inner.receive(timeout)?;
+        self.record(Direction::In, &response)?;
+        Ok(response)
+    }
+}
+
+/// Transport serving the responses of a capture back in order.
+pub struct Replay {
+    records: RefCell<VecDeque<Record>>,
+}
+
+impl Replay {
+    pub fn new(records: Vec<Record>) -> Self {
+        Self {
+            records: RefCell::new(records.into()),
+        }
+    }
+    pub fn from_reader(reader: impl BufRead) -> DeviceResult<Self> {
+        Ok(Self::new(read_capture(reader)?))
+    }
+    pub fn is_done(&self) -> bool {
+        self.records.borrow().is_empty()
+    }
+}
+
+impl Transport for Replay {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        let mut records = self.records.borrow_mut();
+        match records.front() {
+            Some(record) if record.direction == Direction::Out && record.data == report => {
+                records.pop_front();
+                Ok(())
+            }
+            _ => Err(DeviceError::UnexpectedReport(report.to_vec())),
+        }
+    }
+    fn receive(&self, _timeout: Duration) -> DeviceResult<Vec<u8>> {
+        let mut records = self.records.borrow_mut();
+        match records.front() {
+            Some(record) if record.direction == Direction::In => {
+                Ok(records.pop_front().unwrap().data)
+            }
+            _ => Err(DeviceError::Timeout),
+        }
+    }
+}
@@ -2,11 +2,38 @@
 
 use crate::{
     consts::KeystrokeActionFlag,
-    consts::{KeyActionId, KeystrokeType},
-    device::{DeviceResult, UhkCursor},
+    consts::{
+        BacklightingMode, KeyActionId, KeystrokeType, MacroActionId, MacroSubAction,
+        MouseActionParam, PointerFunction, PointerMode, SecondaryRole, SwitchLayerMode,
+        LAYER_NUMBER_TO_STRING,
+    },
+    device::{DeviceError, DeviceResult, ParseError, PathSegment, UhkCursor, UhkWriter, Version},
+    scancodes,
 };
+use bitflags::bitflags;
+use num_enum::TryFromPrimitive;
+use std::fmt;
+
+/// Reads a one byte enum, reporting unknown ids as parse errors.
+fn read_enum<E: TryFromPrimitive<Primitive = u8>>(
+    cursor: &mut UhkCursor,
+    what: &'static str,
+) -> DeviceResult<E> {
+    let offset = cursor.position();
+    let id = cursor.read_u8()?;
+    E::try_from_primitive(id).map_err(|_| {
+        ParseError {
+            what,
+            id,
+            offset,
+            path: Default::default(),
+        }
+        .into()
+    })
+}
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HardwareConfig {
     pub signature: String,
     pub major: u8,
@@ -42,9 +69,22 @@ impl HardwareConfig {
             iso_mode,
         })
     }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_string(&self.signature)?;
+        writer.write_u8(self.major);
+        writer.write_u8(self.minor);
+        writer.write_u8(self.patch);
+        writer.write_u8(self.brand_id);
+        writer.write_u8(self.device_id);
+        writer.write_u32(self.unique_id);
+        writer.write_bool(self.vendor_mode);
+        writer.write_bool(self.iso_mode);
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MouseConfig {
     pub move_initial_speed: u8,
     pub move_acceleration: u8,
@@ -83,9 +123,186 @@ impl MouseConfig {
             scroll_accelerated_speed,
         })
     }
+    fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_u8(self.move_initial_speed);
+        writer.write_u8(self.move_acceleration);
+        writer.write_u8(self.move_decelerated_speed);
+        writer.write_u8(self.move_base_speed);
+        writer.write_u8(self.move_accelerated_speed);
+        writer.write_u8(self.scroll_initial_speed);
+        writer.write_u8(self.scroll_acceleration);
+        writer.write_u8(self.scroll_decelerated_speed);
+        writer.write_u8(self.scroll_base_speed);
+        writer.write_u8(self.scroll_accelerated_speed);
+        Ok(())
+    }
+}
+
+/// Binary layouts of the user config, one per data model major version.
+/// Parsing yields the same in-memory model for all of them, settings that
+/// a layout lacks taking their default values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DataModel {
+    /// Four layers and no backlighting settings.
+    V5,
+    /// Adds the fn2 to fn5 and modifier layers, the backlighting mode and
+    /// color, and axis locking for pointing modules.
+    V6,
+}
+
+impl DataModel {
+    pub const LATEST: Self = Self::V6;
+
+    /// Layout of configs of a version. Minor versions only append to the
+    /// layout of their major version, so newer ones still map to it.
+    pub fn of(version: Version) -> Option<Self> {
+        match version.major {
+            5 => Some(Self::V5),
+            6 => Some(Self::V6),
+            _ => None,
+        }
+    }
+
+    /// Newest version written in this layout.
+    pub const fn version(self) -> Version {
+        match self {
+            Self::V5 => Version::new(5, 0, 0),
+            Self::V6 => Version::new(6, 0, 0),
+        }
+    }
+
+    pub const fn layer_count(self) -> usize {
+        match self {
+            Self::V5 => 4,
+            Self::V6 => LAYER_NUMBER_TO_STRING.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Backlighting {
+    pub mode: BacklightingMode,
+    /// Color of all keys in uniform mode.
+    pub color: Rgb,
+}
+
+impl Default for Backlighting {
+    fn default() -> Self {
+        Self {
+            mode: BacklightingMode::Functional,
+            color: Rgb {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+        }
+    }
+}
+
+impl Backlighting {
+    fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+        let mode = read_enum(cursor, "backlighting mode")?;
+        let color = Rgb {
+            r: cursor.read_u8()?,
+            g: cursor.read_u8()?,
+            b: cursor.read_u8()?,
+        };
+        Ok(Self { mode, color })
+    }
+    fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_u8(self.mode.into());
+        writer.write_u8(self.color.r);
+        writer.write_u8(self.color.g);
+        writer.write_u8(self.color.b);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModuleConfiguration {
+    pub id: u8,
+    pub pointer_mode: PointerMode,
+    pub decelerated_pointer_speed_multiplier: u8,
+    pub base_pointer_speed_multiplier: u8,
+    pub accelerated_pointer_speed_multiplier: u8,
+    pub angular_shift: u16,
+    pub mod_layer_pointer_function: PointerFunction,
+    pub fn_layer_pointer_function: PointerFunction,
+    pub mouse_layer_pointer_function: PointerFunction,
+    /// Keeps the pointer on the axis it started moving along.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub cursor_axis_lock: bool,
+    /// Keeps scrolling on the axis it started along.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub scroll_axis_lock: bool,
+}
+
+impl ModuleConfiguration {
+    pub fn deserialize(cursor: &mut UhkCursor, model: DataModel) -> DeviceResult<Self> {
+        let id = cursor.read_u8()?;
+        let pointer_mode = read_enum(cursor, "pointer mode")?;
+        let decelerated_pointer_speed_multiplier = cursor.read_u8()?;
+        let base_pointer_speed_multiplier = cursor.read_u8()?;
+        let accelerated_pointer_speed_multiplier = cursor.read_u8()?;
+        let angular_shift = cursor.read_u16()?;
+        let mod_layer_pointer_function = read_enum(cursor, "pointer function")?;
+        let fn_layer_pointer_function = read_enum(cursor, "pointer function")?;
+        let mouse_layer_pointer_function = read_enum(cursor, "pointer function")?;
+        let (cursor_axis_lock, scroll_axis_lock) = if model >= DataModel::V6 {
+            (cursor.read_bool()?, cursor.read_bool()?)
+        } else {
+            (false, false)
+        };
+        Ok(Self {
+            id,
+            pointer_mode,
+            decelerated_pointer_speed_multiplier,
+            base_pointer_speed_multiplier,
+            accelerated_pointer_speed_multiplier,
+            angular_shift,
+            mod_layer_pointer_function,
+            fn_layer_pointer_function,
+            mouse_layer_pointer_function,
+            cursor_axis_lock,
+            scroll_axis_lock,
+        })
+    }
+    pub fn serialize(&self, writer: &mut UhkWriter, model: DataModel) -> DeviceResult<()> {
+        writer.write_u8(self.id);
+        writer.write_u8(self.pointer_mode.into());
+        writer.write_u8(self.decelerated_pointer_speed_multiplier);
+        writer.write_u8(self.base_pointer_speed_multiplier);
+        writer.write_u8(self.accelerated_pointer_speed_multiplier);
+        writer.write_u16(self.angular_shift);
+        writer.write_u8(self.mod_layer_pointer_function.into());
+        writer.write_u8(self.fn_layer_pointer_function.into());
+        writer.write_u8(self.mouse_layer_pointer_function.into());
+        if model >= DataModel::V6 {
+            writer.write_bool(self.cursor_axis_lock);
+            writer.write_bool(self.scroll_axis_lock);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UserConfig {
     pub major: u16,
     pub minor: u16,
@@ -98,15 +315,33 @@ pub struct UserConfig {
     pub icons_and_layer_texts_brightness: u8,
     pub alphanumeric_segments_brighrness: u8,
     pub key_backlight_brightness: u8,
+    pub backlighting: Backlighting,
 
     pub mouse_config: MouseConfig,
+
+    pub module_configurations: Vec<ModuleConfiguration>,
+    pub macros: Vec<Macro>,
+    pub keymaps: Vec<KeymapConfig>,
 }
 
 impl UserConfig {
+    /// Whether configs of a data model version can be read and written
+    /// without losing settings: the layout has to be known and the minor
+    /// version must not be newer than the one it was last extended in.
+    pub fn supports(version: Version) -> bool {
+        DataModel::of(version).is_some_and(|model| version.minor <= model.version().minor)
+    }
+
+    pub fn version(&self) -> Version {
+        Version::new(self.major, self.minor, self.patch)
+    }
+
     pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
         let major = cursor.read_u16()?;
         let minor = cursor.read_u16()?;
         let patch = cursor.read_u16()?;
+        let version = Version::new(major, minor, patch);
+        let model = DataModel::of(version).ok_or(DeviceError::UnsupportedDataModel(version))?;
 
         let length = cursor.read_u16()?;
         let name = cursor.read_string()?;
@@ -115,9 +350,35 @@ impl UserConfig {
         let icons_and_layer_texts_brightness = cursor.read_u8()?;
         let alphanumeric_segments_brighrness = cursor.read_u8()?;
         let key_backlight_brightness = cursor.read_u8()?;
+        let backlighting = if model >= DataModel::V6 {
+            Backlighting::deserialize(cursor)?
+        } else {
+            Backlighting::default()
+        };
 
         let mouse_config = MouseConfig::deserialize(cursor)?;
 
+        let n = cursor.read_compact_length()?;
+        let module_configurations = (0..n as usize)
+            .map(|i| {
+                ModuleConfiguration::deserialize(cursor, model)
+                    .map_err(|e| e.context(PathSegment::ModuleConfiguration(i)))
+            })
+            .try_collect()?;
+
+        let n = cursor.read_compact_length()?;
+        let macros = (0..n as usize)
+            .map(|i| Macro::deserialize(cursor).map_err(|e| e.context(PathSegment::Macro(i))))
+            .try_collect()?;
+
+        let n = cursor.read_compact_length()?;
+        let keymaps = (0..n as usize)
+            .map(|i| {
+                KeymapConfig::deserialize(cursor, model)
+                    .map_err(|e| e.context(PathSegment::Keymap(i)))
+            })
+            .try_collect()?;
+
         Ok(Self {
             major,
             minor,
@@ -128,12 +389,238 @@ impl UserConfig {
             icons_and_layer_texts_brightness,
             alphanumeric_segments_brighrness,
             key_backlight_brightness,
+            backlighting,
             mouse_config,
+            module_configurations,
+            macros,
+            keymaps,
         })
     }
+    /// Serializes the config in the layout of its version, writing its
+    /// actual length in place of `length`.
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        let version = self.version();
+        let model = DataModel::of(version).ok_or(DeviceError::UnsupportedDataModel(version))?;
+        let start = writer.position();
+        writer.write_u16(self.major);
+        writer.write_u16(self.minor);
+        writer.write_u16(self.patch);
+
+        writer.write_u16(self.length);
+        writer.write_string(&self.name)?;
+
+        writer.write_u16(self.double_tap_switch_layer_timeout);
+        writer.write_u8(self.icons_and_layer_texts_brightness);
+        writer.write_u8(self.alphanumeric_segments_brighrness);
+        writer.write_u8(self.key_backlight_brightness);
+        if model >= DataModel::V6 {
+            self.backlighting.serialize(writer)?;
+        }
+
+        self.mouse_config.serialize(writer)?;
+
+        writer.write_compact_length(self.module_configurations.len())?;
+        for module_configuration in &self.module_configurations {
+            module_configuration.serialize(writer, model)?;
+        }
+
+        writer.write_compact_length(self.macros.len())?;
+        for macro_ in &self.macros {
+            macro_.serialize(writer)?;
+        }
+
+        writer.write_compact_length(self.keymaps.len())?;
+        for keymap in &self.keymaps {
+            keymap.serialize(writer)?;
+        }
+
+        let length = writer.position() - start;
+        let length = u16::try_from(length).map_err(|_| DeviceError::Overflow {
+            what: "config length",
+            value: length,
+        })?;
+        writer.patch_u16(start + 6, length);
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Macro {
+    pub looped: bool,
+    pub private: bool,
+    pub name: String,
+    pub actions: Vec<MacroAction>,
+}
+
+impl Macro {
+    pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+        let looped = cursor.read_bool()?;
+        let private = cursor.read_bool()?;
+        let name = cursor.read_string()?;
+        let n = cursor.read_compact_length()?;
+        let actions = (0..n as usize)
+            .map(|i| {
+                MacroAction::deserialize(cursor).map_err(|e| e.context(PathSegment::Action(i)))
+            })
+            .try_collect()?;
+        Ok(Self {
+            looped,
+            private,
+            name,
+            actions,
+        })
+    }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_bool(self.looped);
+        writer.write_bool(self.private);
+        writer.write_string(&self.name)?;
+        writer.write_compact_length(self.actions.len())?;
+        for action in &self.actions {
+            action.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MacroAction {
+    Key {
+        action: MacroSubAction,
+        atype: KeystrokeType,
+        scancode: Option<Scancode>,
+        modifiers: Option<Modifiers>,
+    },
+    MouseButton {
+        action: MacroSubAction,
+        buttons: u8,
+    },
+    MoveMouse {
+        x: i16,
+        y: i16,
+    },
+    ScrollMouse {
+        x: i16,
+        y: i16,
+    },
+    Delay(u16),
+    Text(String),
+    Command(String),
+}
+
+impl MacroAction {
+    pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+        let offset = cursor.position();
+        let id = cursor.read_u8()?;
+        let unknown = || ParseError {
+            what: "macro action",
+            id,
+            offset,
+            path: Default::default(),
+        };
+        if id <= MacroActionId::LastKeyMacroAction.into() {
+            // 2 bits action, 2 bits keystroke type, then modifiers and scancode flags
+            let flags = id - u8::from(MacroActionId::KeyMacroAction);
+            let action = MacroSubAction::try_from(flags & 0b11).map_err(|_| unknown())?;
+            let atype = KeystrokeType::try_from(flags >> 2 & 0b11).map_err(|_| unknown())?;
+            let scancode = if flags >> 4 & 0b10 != 0 {
+                Some(Scancode::deserialize(cursor, atype)?)
+            } else {
+                None
+            };
+            let modifiers = if flags >> 4 & 0b01 != 0 {
+                Some(Modifiers::from_bits_retain(cursor.read_u8()?))
+            } else {
+                None
+            };
+            Ok(Self::Key {
+                action,
+                atype,
+                scancode,
+                modifiers,
+            })
+        } else if id <= MacroActionId::LastMouseButtonMacroAction.into() {
+            let action =
+                MacroSubAction::try_from(id - u8::from(MacroActionId::MouseButtonMacroAction))
+                    .map_err(|_| unknown())?;
+            let buttons = cursor.read_u8()?;
+            Ok(Self::MouseButton { action, buttons })
+        } else if id == u8::from(MacroActionId::MoveMouseMacroAction) {
+            let x = cursor.read_i16()?;
+            let y = cursor.read_i16()?;
+            Ok(Self::MoveMouse { x, y })
+        } else if id == u8::from(MacroActionId::ScrollMouseMacroAction) {
+            let x = cursor.read_i16()?;
+            let y = cursor.read_i16()?;
+            Ok(Self::ScrollMouse { x, y })
+        } else if id == u8::from(MacroActionId::DelayMacroAction) {
+            Ok(Self::Delay(cursor.read_u16()?))
+        } else if id == u8::from(MacroActionId::TextMacroAction) {
+            Ok(Self::Text(cursor.read_string()?))
+        } else if id == u8::from(MacroActionId::CommandMacroAction) {
+            Ok(Self::Command(cursor.read_string()?))
+        } else {
+            Err(unknown().into())
+        }
+    }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        match self {
+            Self::Key {
+                action,
+                atype,
+                scancode,
+                modifiers,
+            } => {
+                let mut flags = u8::from(*action) | u8::from(*atype) << 2;
+                if scancode.is_some() {
+                    flags |= 0b10 << 4;
+                }
+                if modifiers.is_some() {
+                    flags |= 0b01 << 4;
+                }
+                writer.write_u8(u8::from(MacroActionId::KeyMacroAction) + flags);
+                if let Some(scancode) = scancode {
+                    scancode.serialize(writer, *atype)?;
+                }
+                if let Some(modifiers) = modifiers {
+                    writer.write_u8(modifiers.bits());
+                }
+            }
+            Self::MouseButton { action, buttons } => {
+                writer
+                    .write_u8(u8::from(MacroActionId::MouseButtonMacroAction) + u8::from(*action));
+                writer.write_u8(*buttons);
+            }
+            Self::MoveMouse { x, y } => {
+                writer.write_u8(MacroActionId::MoveMouseMacroAction.into());
+                writer.write_i16(*x);
+                writer.write_i16(*y);
+            }
+            Self::ScrollMouse { x, y } => {
+                writer.write_u8(MacroActionId::ScrollMouseMacroAction.into());
+                writer.write_i16(*x);
+                writer.write_i16(*y);
+            }
+            Self::Delay(delay) => {
+                writer.write_u8(MacroActionId::DelayMacroAction.into());
+                writer.write_u16(*delay);
+            }
+            Self::Text(text) => {
+                writer.write_u8(MacroActionId::TextMacroAction.into());
+                writer.write_string(text)?;
+            }
+            Self::Command(command) => {
+                writer.write_u8(MacroActionId::CommandMacroAction.into());
+                writer.write_string(command)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeymapConfig {
     pub abbr: String,
     pub default: bool,
@@ -143,14 +630,17 @@ pub struct KeymapConfig {
 }
 
 impl KeymapConfig {
-    pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+    pub fn deserialize(cursor: &mut UhkCursor, model: DataModel) -> DeviceResult<Self> {
         let abbr = cursor.read_string()?;
         let default = cursor.read_bool()?;
         let name = cursor.read_string()?;
         let desc = cursor.read_string()?;
         let n = cursor.read_compact_length()?;
-        let layers = (0..n)
-            .map(|_| LayerConfig::deserialize(cursor))
+        let layers = (0..n as usize)
+            .map(|i| {
+                LayerConfig::deserialize(cursor, model)
+                    .map_err(|e| e.context(PathSegment::Layer(i)))
+            })
             .try_collect()?;
         Ok(Self {
             abbr,
@@ -160,26 +650,59 @@ impl KeymapConfig {
             layers,
         })
     }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_string(&self.abbr)?;
+        writer.write_bool(self.default);
+        writer.write_string(&self.name)?;
+        writer.write_string(&self.desc)?;
+        writer.write_compact_length(self.layers.len())?;
+        for layer in &self.layers {
+            layer.serialize(writer)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LayerConfig {
     pub id: u8,
     pub modules: Vec<ModuleConfig>,
 }
 
 impl LayerConfig {
-    pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+    pub fn deserialize(cursor: &mut UhkCursor, model: DataModel) -> DeviceResult<Self> {
+        let offset = cursor.position();
         let id = cursor.read_u8()?;
+        if id as usize >= model.layer_count() {
+            return Err(ParseError {
+                what: "layer",
+                id,
+                offset,
+                path: Default::default(),
+            }
+            .into());
+        }
         let n = cursor.read_compact_length()?;
-        let modules = (0..n)
-            .map(|_| ModuleConfig::deserialize(cursor))
+        let modules = (0..n as usize)
+            .map(|i| {
+                ModuleConfig::deserialize(cursor).map_err(|e| e.context(PathSegment::Module(i)))
+            })
             .try_collect()?;
         Ok(Self { id, modules })
     }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_u8(self.id);
+        writer.write_compact_length(self.modules.len())?;
+        for module in &self.modules {
+            module.serialize(writer)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleConfig {
     pub id: u8,
     pub key_actions: Vec<KeyAction>,
@@ -189,65 +712,257 @@ impl ModuleConfig {
     pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
         let id = cursor.read_u8()?;
         let n = cursor.read_compact_length()?;
-        let key_actions = (0..n)
-            .map(|_| KeyAction::deserialize(cursor))
+        let key_actions = (0..n as usize)
+            .map(|i| KeyAction::deserialize(cursor).map_err(|e| e.context(PathSegment::Key(i))))
             .try_collect()?;
         Ok(Self { id, key_actions })
     }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        writer.write_u8(self.id);
+        writer.write_compact_length(self.key_actions.len())?;
+        for key_action in &self.key_actions {
+            key_action.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scancode(pub u16);
+
+impl Scancode {
+    pub fn name(&self, atype: KeystrokeType) -> Option<&'static str> {
+        scancodes::name(atype, self.0)
+    }
+    fn deserialize(cursor: &mut UhkCursor, atype: KeystrokeType) -> DeviceResult<Self> {
+        Ok(Self(if atype == KeystrokeType::LongMedia {
+            cursor.read_u16()?
+        } else {
+            cursor.read_u8()?.into()
+        }))
+    }
+    fn serialize(&self, writer: &mut UhkWriter, atype: KeystrokeType) -> DeviceResult<()> {
+        if atype == KeystrokeType::LongMedia {
+            writer.write_u16(self.0);
+        } else {
+            let scancode = u8::try_from(self.0).map_err(|_| DeviceError::Overflow {
+                what: "scancode",
+                value: self.0.into(),
+            })?;
+            writer.write_u8(scancode);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Scancode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#04x}", self.0)
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Modifiers: u8 {
+        const LCTRL = 1 << 0;
+        const LSHIFT = 1 << 1;
+        const LALT = 1 << 2;
+        const LGUI = 1 << 3;
+        const RCTRL = 1 << 4;
+        const RSHIFT = 1 << 5;
+        const RALT = 1 << 6;
+        const RGUI = 1 << 7;
+    }
+}
+
+impl fmt::Display for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [&str; 8] = [
+            "LCtrl", "LShift", "LAlt", "LGui", "RCtrl", "RShift", "RAlt", "RGui",
+        ];
+        let names: Vec<_> = (0..8)
+            .filter(|bit| self.bits() & 1 << bit != 0)
+            .map(|bit| NAMES[bit])
+            .collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keystroke {
+    pub atype: KeystrokeType,
+    pub scancode: Option<Scancode>,
+    pub modifiers: Option<Modifiers>,
+    pub secondary_role: Option<SecondaryRole>,
+}
+
+impl Keystroke {
+    fn deserialize(
+        cursor: &mut UhkCursor,
+        flags: u8,
+        unknown: impl Fn() -> ParseError,
+    ) -> DeviceResult<Self> {
+        let atype = KeystrokeType::try_from(flags >> 3 & 0b11).map_err(|_| unknown())?;
+        let scancode = if flags & u8::from(KeystrokeActionFlag::Scancode) != 0 {
+            Some(Scancode::deserialize(cursor, atype)?)
+        } else {
+            None
+        };
+        let modifiers = if flags & u8::from(KeystrokeActionFlag::ModifierMask) != 0 {
+            Some(Modifiers::from_bits_retain(cursor.read_u8()?))
+        } else {
+            None
+        };
+        let secondary_role = if flags & u8::from(KeystrokeActionFlag::SecondaryRoleAction) != 0 {
+            Some(read_enum(cursor, "secondary role")?)
+        } else {
+            None
+        };
+        Ok(Self {
+            atype,
+            scancode,
+            modifiers,
+            secondary_role,
+        })
+    }
+    fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        let mut flags = u8::from(self.atype) << 3;
+        if self.scancode.is_some() {
+            flags |= u8::from(KeystrokeActionFlag::Scancode);
+        }
+        if self.modifiers.is_some() {
+            flags |= u8::from(KeystrokeActionFlag::ModifierMask);
+        }
+        if self.secondary_role.is_some() {
+            flags |= u8::from(KeystrokeActionFlag::SecondaryRoleAction);
+        }
+        writer.write_u8(u8::from(KeyActionId::NoneAction) + flags);
+        if let Some(scancode) = self.scancode {
+            scancode.serialize(writer, self.atype)?;
+        }
+        if let Some(modifiers) = self.modifiers {
+            writer.write_u8(modifiers.bits());
+        }
+        if let Some(role) = self.secondary_role {
+            writer.write_u8(role.into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Keystroke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = self.modifiers.filter(|modifiers| !modifiers.is_empty());
+        let key = self
+            .scancode
+            .map(|scancode| match scancode.name(self.atype) {
+                Some(name) => name.to_string(),
+                None => scancode.to_string(),
+            });
+        match (modifiers, key) {
+            (Some(modifiers), Some(key)) => write!(f, "{}+{}", modifiers, key)?,
+            (Some(modifiers), None) => write!(f, "{}", modifiers)?,
+            (None, Some(key)) => write!(f, "{}", key)?,
+            (None, None) => write!(f, "-")?,
+        }
+        if let Some(role) = self.secondary_role {
+            write!(f, " / hold: {}", role)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum KeyAction {
     None,
-    Keystroke(Option<u16>, Option<u8>, Option<u8>),
-    SwitchLayer(u8, u8),
+    Keystroke(Keystroke),
+    SwitchLayer(u8, SwitchLayerMode),
     SwitchKeymap(u8),
-    MouseAction(u8),
-    PlayMacroAction,
+    MouseAction(MouseActionParam),
+    PlayMacroAction(u8),
 }
 
 impl KeyAction {
     pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+        let offset = cursor.position();
         let action_id = cursor.read_u8()?;
-        if action_id == KeyActionId::NoneAction.into() {
+        let unknown = || ParseError {
+            what: "key action",
+            id: action_id,
+            offset,
+            path: Default::default(),
+        };
+        if action_id == u8::from(KeyActionId::NoneAction) {
             Ok(Self::None)
         } else if action_id >= KeyActionId::KeystrokeAction.into()
-            && action_id < KeyActionId::LastKeystrokeAction.into()
+            && action_id <= KeyActionId::LastKeystrokeAction.into()
         {
             let flags = action_id - u8::from(KeyActionId::NoneAction);
-            let atype = flags >> 3 & 0b11;
-            let scancode = if flags & u8::from(KeystrokeActionFlag::Scancode) != 0 {
-                Some(if atype == KeystrokeType::LongMedia.into() {
-                    cursor.read_u16()?
-                } else {
-                    cursor.read_u8()?.into()
-                })
-            } else {
-                None
-            };
-            let mask = if flags & u8::from(KeystrokeActionFlag::ModifierMask) != 0 {
-                Some(cursor.read_u8()?)
-            } else {
-                None
-            };
-            let role = if flags & u8::from(KeystrokeActionFlag::SecondaryRoleAction) != 0 {
-                Some(cursor.read_u8()?)
-            } else {
-                None
-            };
-            Ok(Self::Keystroke(scancode, mask, role))
-        } else if action_id == KeyActionId::SwitchLayerAction.into() {
+            Ok(Self::Keystroke(Keystroke::deserialize(
+                cursor, flags, unknown,
+            )?))
+        } else if action_id == u8::from(KeyActionId::SwitchLayerAction) {
             let layer = cursor.read_u8()?;
-            let mode = cursor.read_u8()?;
+            let mode = read_enum(cursor, "switch layer mode")?;
             Ok(Self::SwitchLayer(layer, mode))
-        } else if action_id == KeyActionId::SwitchKeymapAction.into() {
+        } else if action_id == u8::from(KeyActionId::SwitchKeymapAction) {
             let keymap = cursor.read_u8()?;
             Ok(Self::SwitchKeymap(keymap))
-        } else if action_id == KeyActionId::MouseAction.into() {
-            let mouse = cursor.read_u8()?;
+        } else if action_id == u8::from(KeyActionId::MouseAction) {
+            let mouse = read_enum(cursor, "mouse action")?;
             Ok(Self::MouseAction(mouse))
+        } else if action_id == u8::from(KeyActionId::PlayMacroAction) {
+            let index = cursor.read_u8()?;
+            Ok(Self::PlayMacroAction(index))
         } else {
-            unimplemented!()
+            Err(unknown().into())
+        }
+    }
+    pub fn serialize(&self, writer: &mut UhkWriter) -> DeviceResult<()> {
+        match self {
+            Self::None => writer.write_u8(KeyActionId::NoneAction.into()),
+            Self::Keystroke(keystroke) => keystroke.serialize(writer)?,
+            Self::SwitchLayer(layer, mode) => {
+                writer.write_u8(KeyActionId::SwitchLayerAction.into());
+                writer.write_u8(*layer);
+                writer.write_u8((*mode).into());
+            }
+            Self::SwitchKeymap(keymap) => {
+                writer.write_u8(KeyActionId::SwitchKeymapAction.into());
+                writer.write_u8(*keymap);
+            }
+            Self::MouseAction(mouse) => {
+                writer.write_u8(KeyActionId::MouseAction.into());
+                writer.write_u8((*mouse).into());
+            }
+            Self::PlayMacroAction(index) => {
+                writer.write_u8(KeyActionId::PlayMacroAction.into());
+                writer.write_u8(*index);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for KeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "-"),
+            Self::Keystroke(keystroke) => write!(f, "{}", keystroke),
+            Self::SwitchLayer(layer, mode) => {
+                match LAYER_NUMBER_TO_STRING.get(*layer as usize) {
+                    Some(name) => write!(f, "layer {}", name)?,
+                    None => write!(f, "layer {}", layer)?,
+                }
+                write!(f, " ({:?})", mode)
+            }
+            Self::SwitchKeymap(keymap) => write!(f, "keymap #{}", keymap),
+            Self::MouseAction(mouse) => write!(f, "mouse {:?}", mouse),
+            Self::PlayMacroAction(index) => write!(f, "macro #{}", index),
         }
     }
 }
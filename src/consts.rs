@@ -2,7 +2,8 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 pub const MAX_PAYLOAD_SIZE: usize = 64;
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ModuleSlots {
     NoModule = 0,
@@ -13,7 +14,21 @@ pub enum ModuleSlots {
     TouchpadRight = 5,
 }
 
-#[derive(IntoPrimitive)]
+/// Module ids used by the per-module key action lists of a layer.
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum ModuleId {
+    RightKeyboardHalf = 0,
+    LeftKeyboardHalf = 1,
+    KeyClusterLeft = 2,
+    TrackballRight = 3,
+    TrackpointRight = 4,
+    TouchpadRight = 5,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum UsbCommand {
     GetProperty = 0x00,
@@ -39,12 +54,49 @@ pub enum UsbCommand {
     ExecMacroCommand = 0x14,
 }
 
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum UsbStatusCode {
+    Success = 0,
+    InvalidCommand = 1,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum ReadConfigStatus {
+    InvalidConfigBufferId = 2,
+    LengthTooLarge = 3,
+    BufferOutOfBounds = 4,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum WriteConfigStatus {
+    LengthTooLarge = 2,
+    BufferOutOfBounds = 3,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum SwitchKeymapStatus {
+    InvalidAbbreviationLength = 2,
+    InvalidAbbreviation = 3,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
 pub enum EepromOperation {
     Read = 0,
     Write = 1,
 }
 
 #[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ConfigBufferId {
     HardwareConfig = 0,
@@ -52,7 +104,8 @@ pub enum ConfigBufferId {
     ValidatedUserConfig = 2,
 }
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum DevicePropertyIds {
     DeviceProtocolVersion = 0,
@@ -65,6 +118,7 @@ pub enum DevicePropertyIds {
     GitRepo = 7,
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EnumerationModes {
     Bootloader = 0,
     Buspal = 1,
@@ -72,13 +126,17 @@ pub enum EnumerationModes {
     CompatibleKeyboard = 3,
 }
 
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
 pub enum KbootCommands {
     Idle = 0,
     Ping = 1,
     Reset = 2,
 }
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ModulePropertyId {
     ProtocolVersions = 0,
@@ -86,7 +144,8 @@ pub enum ModulePropertyId {
     GitRepo = 2,
 }
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum UsbVariables {
     TestSwitches = 0,
@@ -97,6 +156,7 @@ pub enum UsbVariables {
 }
 
 #[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum MacroActionId {
     KeyMacroAction = 0,
@@ -122,7 +182,17 @@ pub enum MacroActionId {
     CommandMacroAction = 71,
 }
 
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum MacroSubAction {
+    Press = 0,
+    Hold = 1,
+    Release = 2,
+}
+
 #[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum KeyActionId {
     NoneAction = 0,
@@ -143,6 +213,7 @@ pub enum KeyActionId {
 }
 
 #[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum KeystrokeActionFlag {
     Scancode = 1 << 0,
@@ -150,7 +221,8 @@ pub enum KeystrokeActionFlag {
     SecondaryRoleAction = 1 << 2,
 }
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum KeystrokeType {
     Basic = 0,
@@ -159,7 +231,43 @@ pub enum KeystrokeType {
     System = 3,
 }
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum SecondaryRole {
+    LeftCtrl = 0,
+    LeftShift = 1,
+    LeftAlt = 2,
+    LeftSuper = 3,
+    RightCtrl = 4,
+    RightShift = 5,
+    RightAlt = 6,
+    RightSuper = 7,
+    Mod = 8,
+    Fn = 9,
+    Mouse = 10,
+}
+
+impl std::fmt::Display for SecondaryRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::LeftCtrl => "LCtrl",
+            Self::LeftShift => "LShift",
+            Self::LeftAlt => "LAlt",
+            Self::LeftSuper => "LGui",
+            Self::RightCtrl => "RCtrl",
+            Self::RightShift => "RShift",
+            Self::RightAlt => "RAlt",
+            Self::RightSuper => "RGui",
+            Self::Mod => "Mod",
+            Self::Fn => "Fn",
+            Self::Mouse => "Mouse",
+        })
+    }
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum SwitchLayerMode {
     HoldAndDoubleTapToggle = 0,
@@ -167,7 +275,24 @@ pub enum SwitchLayerMode {
     Hold = 2,
 }
 
-#[derive(IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum PointerMode {
+    Move = 0,
+    Scroll = 1,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum PointerFunction {
+    Move = 0,
+    Scroll = 1,
+}
+
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum MouseActionParam {
     LeftClick = 0,
@@ -190,4 +315,17 @@ pub enum MouseActionParam {
     Button8,
 }
 
-pub const LAYER_NUMBER_TO_STRING: [&str; 4] = ["base", "mod", "fn", "mouse"];
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum BacklightingMode {
+    /// Keys are lit by the kind of action they are assigned.
+    #[default]
+    Functional = 0,
+    /// Every key is lit with the same color.
+    Uniform = 1,
+}
+
+pub const LAYER_NUMBER_TO_STRING: [&str; 12] = [
+    "base", "mod", "fn", "mouse", "fn2", "fn3", "fn4", "fn5", "shift", "control", "alt", "super",
+];
@@ -1,10 +1,13 @@
+use crate::config::UserConfig;
 use crate::consts::{
-    self, ConfigBufferId, DevicePropertyIds, ModulePropertyId, ModuleSlots, UsbVariables,
+    self, ConfigBufferId, DevicePropertyIds, EepromOperation, KbootCommands, ModulePropertyId,
+    ModuleSlots, UsbCommand, UsbVariables,
 };
-use byteorder::{LittleEndian, ReadBytesExt};
+use crate::transport::Transport;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use hidapi::{HidDevice, HidError};
 use num_enum::TryFromPrimitiveError;
-use std::{cmp::min, io::Read, string::FromUtf8Error, time::Duration};
+use std::{cmp::min, fmt, io::Read, string::FromUtf8Error, time::Duration};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,23 +16,137 @@ pub enum DeviceError {
     Hid(#[from] HidError),
     #[error("module slot error")]
     LeftSlot(#[from] TryFromPrimitiveError<ModuleSlots>),
+    #[error("kboot command error")]
+    KbootCommand(#[from] TryFromPrimitiveError<KbootCommands>),
     #[error("io error")]
     IO(#[from] std::io::Error),
     #[error("from utf8 error")]
     Utf8Error(#[from] FromUtf8Error),
+    #[error("unexpected report {0:02x?}")]
+    UnexpectedReport(Vec<u8>),
+    #[error("timed out waiting for response")]
+    Timeout,
+    #[error("malformed capture at line {0}")]
+    Capture(usize),
+    #[error("parse error: {0}")]
+    Parse(#[from] ParseError),
+    #[error("{0:?} failed with status {1}")]
+    Status(UsbCommand, u8),
+    #[error("{command:?} response has {actual} bytes, expected {expected}")]
+    ShortResponse {
+        command: UsbCommand,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("config rejected with code {code} at offset {offset}")]
+    ApplyConfig { code: u8, offset: u16 },
+    #[error("config of {size} bytes exceeds the {capacity} byte user config buffer")]
+    ConfigTooLarge { size: usize, capacity: usize },
+    #[error("{what} of {value} does not fit the config format")]
+    Overflow { what: &'static str, value: usize },
+    #[error("data model {0} is not supported")]
+    UnsupportedDataModel(Version),
+    #[error("config has data model {config} but the keyboard expects {device}")]
+    DataModelMismatch { config: Version, device: Version },
+}
+
+impl DeviceError {
+    /// Prefixes the config path of a parse error with an enclosing element.
+    pub fn context(mut self, segment: PathSegment) -> Self {
+        if let Self::Parse(ref mut err) = self {
+            err.path.0.insert(0, segment);
+        }
+        self
+    }
 }
 
 pub type DeviceResult<T> = Result<T, DeviceError>;
 
-pub struct Device {
-    dev: HidDevice,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment {
+    Keymap(usize),
+    Layer(usize),
+    Module(usize),
+    Key(usize),
+    Macro(usize),
+    Action(usize),
+    ModuleConfiguration(usize),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConfigPath(pub Vec<PathSegment>);
+
+impl fmt::Display for ConfigPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "config");
+        }
+        for (i, segment) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            match segment {
+                PathSegment::Keymap(i) => write!(f, "keymap {}", i)?,
+                PathSegment::Layer(i) => write!(f, "layer {}", i)?,
+                PathSegment::Module(i) => write!(f, "module {}", i)?,
+                PathSegment::Key(i) => write!(f, "key {}", i)?,
+                PathSegment::Macro(i) => write!(f, "macro {}", i)?,
+                PathSegment::Action(i) => write!(f, "action {}", i)?,
+                PathSegment::ModuleConfiguration(i) => write!(f, "module configuration {}", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("unknown {what} id {id} at offset {offset} in {path}")]
+pub struct ParseError {
+    pub what: &'static str,
+    pub id: u8,
+    pub offset: u64,
+    pub path: ConfigPath,
+}
+
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+pub struct Device<T: Transport = HidDevice> {
+    dev: T,
 }
 
-impl Device {
-    pub fn open(dev: HidDevice) -> Self {
+impl<T: Transport> Device<T> {
+    pub fn open(dev: T) -> Self {
         // TODO: allow non blocking
         Self { dev }
     }
+    pub fn into_inner(self) -> T {
+        self.dev
+    }
+    fn request(&self, report: &[u8]) -> DeviceResult<Vec<u8>> {
+        self.dev.send(report)?;
+        self.dev.receive(TIMEOUT)
+    }
+    /// Sends a command, returning its response whatever the status.
+    fn exchange(&self, command: UsbCommand, args: &[u8]) -> DeviceResult<Response> {
+        let mut report = vec![command.into()];
+        report.extend_from_slice(args);
+        let buf = self.request(&report)?;
+        if buf.is_empty() {
+            return Err(DeviceError::ShortResponse {
+                command,
+                expected: 1,
+                actual: 0,
+            });
+        }
+        Ok(Response { command, buf })
+    }
+    fn command(&self, command: UsbCommand, args: &[u8]) -> DeviceResult<Response> {
+        let response = self.exchange(command, args)?;
+        match response.status() {
+            0 => Ok(response),
+            status => Err(DeviceError::Status(command, status)),
+        }
+    }
     pub fn wait(&self) -> DeviceResult<()> {
         while self.state()?.eeprom_busy {
             std::thread::sleep(Duration::from_millis(200));
@@ -41,115 +158,199 @@ impl Device {
         let size = match buffer {
             ConfigBufferId::HardwareConfig => sizes.0,
             _ => sizes.1,
-        } as u16;
-        const CHUNK_SIZE: u16 = 63;
-        let mut offset: u16 = 0;
-        let mut data = vec![];
-        while offset < size {
-            let reading = min(CHUNK_SIZE, size - offset);
-            self.dev.write(&[
-                0x0,
-                consts::UsbCommand::ReadConfig.into(),
-                buffer.into(),
-                CHUNK_SIZE as u8,
-                offset.to_le_bytes()[0],
-                offset.to_le_bytes()[1],
-            ])?;
-            let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-            self.dev.read_timeout(&mut buf, 1000)?;
-            data.extend_from_slice(&buf[1..(reading + 1) as usize]);
-            offset += reading;
+        };
+        // what fits in a report after the status byte
+        const CHUNK_SIZE: usize = consts::MAX_PAYLOAD_SIZE - 1;
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let length = min(CHUNK_SIZE, size - data.len());
+            let offset = (data.len() as u16).to_le_bytes();
+            let response = self.command(
+                UsbCommand::ReadConfig,
+                &[buffer.into(), length as u8, offset[0], offset[1]],
+            )?;
+            data.extend_from_slice(response.take(length)?);
         }
         Ok(data)
     }
+    /// Checks that user configs of the keyboard can be parsed, returning its
+    /// data model version.
+    pub fn check_data_model(&self) -> DeviceResult<Version> {
+        let version = self.protocol_versions()?.data_model;
+        if !UserConfig::supports(version) {
+            return Err(DeviceError::UnsupportedDataModel(version));
+        }
+        Ok(version)
+    }
+    /// Uploads and applies a user config, refusing configs the firmware
+    /// would lay out differently.
+    pub fn upload_user_config(&self, config: &[u8]) -> DeviceResult<()> {
+        let device = self.check_data_model()?;
+        let version = Version::deserialize(&mut UhkCursor::new(config.to_vec()))?;
+        if version.major != device.major || version.minor > device.minor {
+            return Err(DeviceError::DataModelMismatch {
+                config: version,
+                device,
+            });
+        }
+        let (_, capacity) = self.get_config_size()?;
+        if config.len() > capacity {
+            return Err(DeviceError::ConfigTooLarge {
+                size: config.len(),
+                capacity,
+            });
+        }
+        // offsets fit in a u16 as the buffer size does
+        const CHUNK_SIZE: usize = consts::MAX_PAYLOAD_SIZE - 4;
+        for (i, chunk) in config.chunks(CHUNK_SIZE).enumerate() {
+            let offset = ((i * CHUNK_SIZE) as u16).to_le_bytes();
+            let mut args = vec![chunk.len() as u8, offset[0], offset[1]];
+            args.extend_from_slice(chunk);
+            self.command(UsbCommand::WriteStagingUserConfig, &args)?;
+        }
+        // failures carry the offset at which parsing the config stopped
+        let response = self.exchange(UsbCommand::ApplyConfig, &[])?;
+        if response.status() != 0 {
+            let offset = response.take(2)?;
+            return Err(DeviceError::ApplyConfig {
+                code: response.status(),
+                offset: u16::from_le_bytes([offset[0], offset[1]]),
+            });
+        }
+        self.command(
+            UsbCommand::LaunchEepromTransfer,
+            &[
+                EepromOperation::Write.into(),
+                ConfigBufferId::ValidatedUserConfig.into(),
+            ],
+        )?;
+        self.wait()
+    }
+    /// Requests a property of the module in a slot, returning a cursor over
+    /// its value.
     pub fn get_module_property(
         &self,
         module: ModuleSlots,
         property: ModulePropertyId,
-    ) -> DeviceResult<Vec<u8>> {
-        self.dev.write(&[
-            0x0,
-            consts::UsbCommand::GetModuleProperty.into(),
-            module.into(),
-            property.into(),
-        ])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
-        Ok(buf)
+    ) -> DeviceResult<UhkCursor> {
+        Ok(self
+            .command(
+                UsbCommand::GetModuleProperty,
+                &[module.into(), property.into()],
+            )?
+            .into_cursor())
     }
+    /// Sizes of the hardware and user config buffers in bytes.
     pub fn get_config_size(&self) -> DeviceResult<(usize, usize)> {
-        self.dev.write(&[
-            0x0,
-            consts::UsbCommand::GetProperty.into(),
-            DevicePropertyIds::ConfigSizes.into(),
-        ])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
+        let response = self.command(
+            UsbCommand::GetProperty,
+            &[DevicePropertyIds::ConfigSizes.into()],
+        )?;
+        let sizes = response.take(4)?;
         Ok((
-            (buf[1] as usize + (buf[2] as usize)) << 8,
-            (buf[3] as usize + (buf[4] as usize)) << 8,
+            u16::from_le_bytes([sizes[0], sizes[1]]).into(),
+            u16::from_le_bytes([sizes[2], sizes[3]]).into(),
         ))
     }
+    /// Requests a device property of at least `length` bytes, returning a
+    /// cursor over its value.
+    fn get_property(&self, property: DevicePropertyIds, length: usize) -> DeviceResult<UhkCursor> {
+        let response = self.command(UsbCommand::GetProperty, &[property.into()])?;
+        response.take(length)?;
+        Ok(response.into_cursor())
+    }
+    pub fn device_protocol_version(&self) -> DeviceResult<u8> {
+        self.get_property(DevicePropertyIds::DeviceProtocolVersion, 1)?
+            .read_u8()
+    }
+    pub fn protocol_versions(&self) -> DeviceResult<ProtocolVersions> {
+        let mut cursor = self.get_property(DevicePropertyIds::ProtocolVersions, 30)?;
+        Ok(ProtocolVersions {
+            firmware: Version::deserialize(&mut cursor)?,
+            data_model: Version::deserialize(&mut cursor)?,
+            usb_protocol: Version::deserialize(&mut cursor)?,
+            slave_protocol: Version::deserialize(&mut cursor)?,
+            module_protocol: Version::deserialize(&mut cursor)?,
+        })
+    }
+    pub fn current_kboot_command(&self) -> DeviceResult<KbootCommands> {
+        let mut cursor = self.get_property(DevicePropertyIds::CurrentKbootCommand, 1)?;
+        Ok(KbootCommands::try_from(cursor.read_u8()?)?)
+    }
+    pub fn i2c_baud_rate(&self) -> DeviceResult<I2cBaudRate> {
+        let mut cursor = self.get_property(DevicePropertyIds::I2cBaudRate, 9)?;
+        // frequency divider register of the main bus
+        cursor.read_u8()?;
+        Ok(I2cBaudRate {
+            requested: cursor.read_u32()?,
+            actual: cursor.read_u32()?,
+        })
+    }
     pub fn uptime(&self) -> DeviceResult<Duration> {
-        self.dev.write(&[
-            0x0,
-            consts::UsbCommand::GetProperty.into(),
-            DevicePropertyIds::Uptime.into(),
-        ])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
-        let mut num = [0u8; 4];
-        num.copy_from_slice(&buf[1..5]);
-        Ok(Duration::from_millis(u32::from_le_bytes(num).into()))
+        let mut cursor = self.get_property(DevicePropertyIds::Uptime, 4)?;
+        Ok(Duration::from_millis(cursor.read_u32()?.into()))
+    }
+    pub fn git_tag(&self) -> DeviceResult<String> {
+        // at least the terminating nul
+        self.get_property(DevicePropertyIds::GitTag, 1)?
+            .read_nul_terminated()
+    }
+    pub fn git_repo(&self) -> DeviceResult<String> {
+        self.get_property(DevicePropertyIds::GitRepo, 1)?
+            .read_nul_terminated()
     }
     pub fn get_variable(&self, var: UsbVariables) -> DeviceResult<u8> {
-        self.dev
-            .write(&[0x0, consts::UsbCommand::GetVariable.into(), var.into()])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
-        Ok(buf[1])
+        let response = self.command(UsbCommand::GetVariable, &[var.into()])?;
+        Ok(response.take(1)?[0])
+    }
+    pub fn set_variable(&self, var: UsbVariables, value: u8) -> DeviceResult<()> {
+        self.command(UsbCommand::SetVariable, &[var.into(), value])?;
+        Ok(())
+    }
+    pub fn switch_keymap(&self, abbr: &str) -> DeviceResult<()> {
+        let mut args = vec![abbr.len() as u8];
+        args.extend_from_slice(abbr.as_bytes());
+        self.command(UsbCommand::SwitchKeymap, &args)?;
+        Ok(())
+    }
+    pub fn exec_macro_command(&self, command: &str) -> DeviceResult<()> {
+        // nul terminated, truncated to fit a single report
+        let mut args: Vec<u8> = command.bytes().take(consts::MAX_PAYLOAD_SIZE - 2).collect();
+        args.push(0);
+        self.command(UsbCommand::ExecMacroCommand, &args)?;
+        Ok(())
     }
     #[deprecated]
     pub fn set_test_led(&self, state: bool) -> DeviceResult<()> {
-        self.dev.write(&[
-            0x0,
+        self.request(&[
             consts::UsbCommand::SetTestLed.into(),
             if state { 1 } else { 0 },
         ])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
         Ok(())
     }
     #[deprecated]
     pub fn set_brightness(&self, brightness: u8) -> DeviceResult<()> {
-        self.dev.write(&[
-            0x0,
-            consts::UsbCommand::SetLedPwmBrightness.into(),
-            brightness,
-        ])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
+        self.request(&[consts::UsbCommand::SetLedPwmBrightness.into(), brightness])?;
         Ok(())
     }
     pub fn state(&self) -> DeviceResult<DeviceState> {
-        self.dev
-            .write(&[0x0, consts::UsbCommand::GetDeviceState.into()])?;
-        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
-        self.dev.read_timeout(&mut buf, 1000)?;
+        let response = self.command(UsbCommand::GetDeviceState, &[])?;
+        let state = response.take(6)?;
         Ok(DeviceState {
-            eeprom_busy: buf[1] != 0,
-            halves_merged: buf[2] != 0,
-            left_half_connected: buf[3] != 0,
-            active_layer: buf[6] & 0x7f,
-            active_layer_toggled: buf[6] & 0x80 != 0,
-            left_half_slot: buf[3],
-            left_module_slot: ModuleSlots::try_from(buf[4])?,
-            right_module_slot: ModuleSlots::try_from(buf[5])?,
+            eeprom_busy: state[0] != 0,
+            halves_merged: state[1] != 0,
+            left_half_connected: state[2] != 0,
+            active_layer: state[5] & 0x7f,
+            active_layer_toggled: state[5] & 0x80 != 0,
+            left_half_slot: state[2],
+            left_module_slot: ModuleSlots::try_from(state[3])?,
+            right_module_slot: ModuleSlots::try_from(state[4])?,
         })
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceState {
     pub eeprom_busy: bool,
     pub halves_merged: bool,
@@ -161,6 +362,90 @@ pub struct DeviceState {
     pub right_module_slot: ModuleSlots,
 }
 
+/// Response to a command, starting with its status byte.
+#[derive(Debug)]
+pub struct Response {
+    command: UsbCommand,
+    buf: Vec<u8>,
+}
+
+impl Response {
+    pub fn status(&self) -> u8 {
+        self.buf[0]
+    }
+    /// Bytes following the status byte.
+    pub fn payload(&self) -> &[u8] {
+        &self.buf[1..]
+    }
+    /// The first `length` bytes of the payload, failing if the device sent
+    /// fewer.
+    pub fn take(&self, length: usize) -> DeviceResult<&[u8]> {
+        self.payload()
+            .get(..length)
+            .ok_or(DeviceError::ShortResponse {
+                command: self.command,
+                expected: length + 1,
+                actual: self.buf.len(),
+            })
+    }
+    pub fn into_cursor(mut self) -> UhkCursor {
+        self.buf.remove(0);
+        UhkCursor::new(self.buf)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl Version {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+    pub fn deserialize(cursor: &mut UhkCursor) -> DeviceResult<Self> {
+        Ok(Self {
+            major: cursor.read_u16()?,
+            minor: cursor.read_u16()?,
+            patch: cursor.read_u16()?,
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Versions of the firmware running on the right half and of the formats
+/// and protocols it speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtocolVersions {
+    pub firmware: Version,
+    pub data_model: Version,
+    pub usb_protocol: Version,
+    pub slave_protocol: Version,
+    pub module_protocol: Version,
+}
+
+/// Baud rates of the I2C bus connecting the halves and modules, in bits
+/// per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct I2cBaudRate {
+    pub requested: u32,
+    pub actual: u32,
+}
+
 pub struct UhkCursor {
     cursor: std::io::Cursor<Vec<u8>>,
 }
@@ -171,6 +456,9 @@ impl UhkCursor {
             cursor: std::io::Cursor::new(data),
         }
     }
+    pub fn position(&self) -> u64 {
+        self.cursor.position()
+    }
     pub fn read_bool(&mut self) -> DeviceResult<bool> {
         Ok(self.read_u8()? != 0)
     }
@@ -180,6 +468,9 @@ impl UhkCursor {
     pub fn read_u16(&mut self) -> DeviceResult<u16> {
         Ok(self.cursor.read_u16::<LittleEndian>()?)
     }
+    pub fn read_i16(&mut self) -> DeviceResult<i16> {
+        Ok(self.cursor.read_i16::<LittleEndian>()?)
+    }
     pub fn read_u32(&mut self) -> DeviceResult<u32> {
         Ok(self.cursor.read_u32::<LittleEndian>()?)
     }
@@ -191,11 +482,74 @@ impl UhkCursor {
             Ok(length.into())
         }
     }
+    /// Reads a nul terminated string running at most to the end of the data.
+    pub fn read_nul_terminated(&mut self) -> DeviceResult<String> {
+        let mut buf = vec![];
+        while let Ok(byte) = self.cursor.read_u8() {
+            if byte == 0 {
+                break;
+            }
+            buf.push(byte);
+        }
+        Ok(String::from_utf8(buf)?)
+    }
     pub fn read_string(&mut self) -> DeviceResult<String> {
         let length = self.read_compact_length()?;
-        dbg!(length);
         let mut buf = vec![0u8; length as usize];
         self.cursor.read_exact(&mut buf)?;
         Ok(String::from_utf8(buf)?)
     }
 }
+
+#[derive(Default)]
+pub struct UhkWriter {
+    buf: Vec<u8>,
+}
+
+impl UhkWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+    pub fn position(&self) -> usize {
+        self.buf.len()
+    }
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value.into());
+    }
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+    pub fn write_u16(&mut self, value: u16) {
+        self.buf.write_u16::<LittleEndian>(value).unwrap();
+    }
+    pub fn write_i16(&mut self, value: i16) {
+        self.buf.write_i16::<LittleEndian>(value).unwrap();
+    }
+    pub fn write_u32(&mut self, value: u32) {
+        self.buf.write_u32::<LittleEndian>(value).unwrap();
+    }
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        self.buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_compact_length(&mut self, length: usize) -> DeviceResult<()> {
+        if length < 0xff {
+            self.write_u8(length as u8);
+        } else {
+            let length = u16::try_from(length).map_err(|_| DeviceError::Overflow {
+                what: "length",
+                value: length,
+            })?;
+            self.write_u8(0xff);
+            self.write_u16(length);
+        }
+        Ok(())
+    }
+    pub fn write_string(&mut self, value: &str) -> DeviceResult<()> {
+        self.write_compact_length(value.len())?;
+        self.buf.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    config::UserConfig,
+    consts::{
+        self, ConfigBufferId, DevicePropertyIds, EepromOperation, ModulePropertyId, ModuleSlots,
+        ReadConfigStatus, SwitchKeymapStatus, UsbCommand, UsbStatusCode, WriteConfigStatus,
+    },
+    device::{DeviceError, DeviceResult, UhkCursor, Version},
+    transport::Transport,
+};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+pub const HARDWARE_CONFIG_SIZE: usize = 64;
+pub const USER_CONFIG_SIZE: usize = 32704;
+
+const USB_VARIABLE_COUNT: usize = 5;
+
+/// Observable state of the emulated right half.
+#[derive(Clone)]
+pub struct EmulatorState {
+    pub hardware_config: Vec<u8>,
+    pub staging_user_config: Vec<u8>,
+    pub validated_user_config: Vec<u8>,
+    pub eeprom_hardware_config: Vec<u8>,
+    pub eeprom_user_config: Vec<u8>,
+    pub eeprom_busy_time: Duration,
+    pub eeprom_busy_until: Option<Instant>,
+    pub halves_merged: bool,
+    pub left_half_connected: bool,
+    pub left_module_slot: ModuleSlots,
+    pub right_module_slot: ModuleSlots,
+    pub active_layer: u8,
+    pub active_layer_toggled: bool,
+    pub current_keymap: Option<String>,
+    pub variables: [u8; USB_VARIABLE_COUNT],
+    pub macro_commands: Vec<String>,
+    pub device_protocol_version: u8,
+    pub firmware_version: Version,
+    pub data_model_version: Version,
+    pub usb_protocol_version: Version,
+    pub slave_protocol_version: Version,
+    pub module_protocol_version: Version,
+    pub git_tag: String,
+    pub git_repo: String,
+    pub booted: Instant,
+}
+
+impl EmulatorState {
+    fn eeprom_busy(&self) -> bool {
+        self.eeprom_busy_until
+            .is_some_and(|until| Instant::now() < until)
+    }
+}
+
+/// Software model of the right half firmware answering the USB protocol.
+pub struct Emulator {
+    state: RefCell<EmulatorState>,
+    pending: RefCell<VecDeque<Vec<u8>>>,
+}
+
+impl Emulator {
+    pub fn new(hardware_config: &[u8], user_config: &[u8]) -> Self {
+        let hardware_config = padded(hardware_config, HARDWARE_CONFIG_SIZE);
+        let user_config = padded(user_config, USER_CONFIG_SIZE);
+        Self {
+            state: RefCell::new(EmulatorState {
+                hardware_config: hardware_config.clone(),
+                staging_user_config: user_config.clone(),
+                validated_user_config: user_config.clone(),
+                eeprom_hardware_config: hardware_config,
+                eeprom_user_config: user_config,
+                eeprom_busy_time: Duration::from_millis(300),
+                eeprom_busy_until: None,
+                halves_merged: true,
+                left_half_connected: true,
+                left_module_slot: ModuleSlots::NoModule,
+                right_module_slot: ModuleSlots::NoModule,
+                active_layer: 0,
+                active_layer_toggled: false,
+                current_keymap: None,
+                variables: [0; USB_VARIABLE_COUNT],
+                macro_commands: vec![],
+                device_protocol_version: 1,
+                firmware_version: Version::new(9, 0, 0),
+                data_model_version: Version::new(5, 0, 0),
+                usb_protocol_version: Version::new(1, 2, 0),
+                slave_protocol_version: Version::new(4, 0, 0),
+                module_protocol_version: Version::new(4, 0, 0),
+                git_tag: "v9.0.0".to_string(),
+                git_repo: "UltimateHackingKeyboard/firmware".to_string(),
+                booted: Instant::now(),
+            }),
+            pending: RefCell::new(VecDeque::new()),
+        }
+    }
+    pub fn with_eeprom_busy_time(self, busy: Duration) -> Self {
+        self.state.borrow_mut().eeprom_busy_time = busy;
+        self
+    }
+    /// A snapshot of the current state.
+    pub fn state(&self) -> EmulatorState {
+        self.state.borrow().clone()
+    }
+    /// Changes the state, for instance to simulate another firmware version.
+    pub fn update_state(&self, update: impl FnOnce(&mut EmulatorState)) {
+        update(&mut self.state.borrow_mut());
+    }
+    fn handle(&self, report: &[u8]) -> Vec<u8> {
+        let mut response = Response::default();
+        let command = report.first().copied().map(UsbCommand::try_from);
+        let args = report.get(1..).unwrap_or_default();
+        match command {
+            Some(Ok(command)) => self.dispatch(command, args, &mut response),
+            _ => response.status(UsbStatusCode::InvalidCommand),
+        }
+        response.buf.to_vec()
+    }
+    fn dispatch(&self, command: UsbCommand, args: &[u8], response: &mut Response) {
+        let mut state = self.state.borrow_mut();
+        match command {
+            UsbCommand::GetProperty => get_property(&state, arg(args, 0), response),
+            UsbCommand::ReadConfig => read_config(&state, args, response),
+            UsbCommand::WriteHardwareConfig => {
+                write_config(&mut state.hardware_config, args, response)
+            }
+            UsbCommand::WriteStagingUserConfig => {
+                write_config(&mut state.staging_user_config, args, response)
+            }
+            UsbCommand::ApplyConfig => apply_config(&mut state, response),
+            UsbCommand::LaunchEepromTransfer => launch_eeprom_transfer(&mut state, args, response),
+            UsbCommand::GetDeviceState => {
+                response.u8(1, state.eeprom_busy().into());
+                response.u8(2, state.halves_merged.into());
+                response.u8(3, state.left_half_connected.into());
+                response.u8(4, state.left_module_slot.into());
+                response.u8(5, state.right_module_slot.into());
+                response.u8(
+                    6,
+                    state.active_layer | if state.active_layer_toggled { 0x80 } else { 0 },
+                );
+            }
+            UsbCommand::GetModuleProperty => {
+                get_module_property(&state, arg(args, 0), arg(args, 1), response)
+            }
+            UsbCommand::SwitchKeymap => switch_keymap(&mut state, args, response),
+            UsbCommand::GetVariable => match state.variables.get(arg(args, 0) as usize) {
+                Some(value) => response.u8(1, *value),
+                None => response.status(UsbStatusCode::InvalidCommand),
+            },
+            UsbCommand::SetVariable => match state.variables.get_mut(arg(args, 0) as usize) {
+                Some(value) => *value = arg(args, 1),
+                None => response.status(UsbStatusCode::InvalidCommand),
+            },
+            UsbCommand::ExecMacroCommand => {
+                let end = args.iter().position(|b| *b == 0).unwrap_or(args.len());
+                let command = String::from_utf8_lossy(&args[..end]).into_owned();
+                state.macro_commands.push(command);
+            }
+            UsbCommand::SetTestLed | UsbCommand::SetLedPwmBrightness => {}
+            _ => response.status(UsbStatusCode::InvalidCommand),
+        }
+    }
+}
+
+impl Transport for Emulator {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        let response = self.handle(report);
+        self.pending.borrow_mut().push_back(response);
+        Ok(())
+    }
+    fn receive(&self, _timeout: Duration) -> DeviceResult<Vec<u8>> {
+        self.pending
+            .borrow_mut()
+            .pop_front()
+            .ok_or(DeviceError::Timeout)
+    }
+}
+
+struct Response {
+    buf: [u8; consts::MAX_PAYLOAD_SIZE],
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self {
+            buf: [0; consts::MAX_PAYLOAD_SIZE],
+        }
+    }
+}
+
+impl Response {
+    fn status(&mut self, status: impl Into<u8>) {
+        self.buf[0] = status.into();
+    }
+    fn u8(&mut self, offset: usize, value: u8) {
+        self.buf[offset] = value;
+    }
+    fn u16(&mut self, offset: usize, value: u16) {
+        self.buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+    fn u32(&mut self, offset: usize, value: u32) {
+        self.buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    fn version(&mut self, offset: usize, version: Version) {
+        self.u16(offset, version.major);
+        self.u16(offset + 2, version.minor);
+        self.u16(offset + 4, version.patch);
+    }
+    fn string(&mut self, offset: usize, value: &str) {
+        // always leave room for the terminating nul
+        let len = value.len().min(self.buf.len() - offset - 1);
+        self.buf[offset..offset + len].copy_from_slice(&value.as_bytes()[..len]);
+        self.buf[offset + len] = 0;
+    }
+}
+
+fn arg(args: &[u8], index: usize) -> u8 {
+    args.get(index).copied().unwrap_or_default()
+}
+
+fn padded(data: &[u8], size: usize) -> Vec<u8> {
+    let mut buf = data.to_vec();
+    buf.resize(size, 0);
+    buf
+}
+
+fn get_property(state: &EmulatorState, property: u8, response: &mut Response) {
+    match DevicePropertyIds::try_from(property) {
+        Ok(DevicePropertyIds::DeviceProtocolVersion) => {
+            response.u8(1, state.device_protocol_version)
+        }
+        Ok(DevicePropertyIds::ProtocolVersions) => {
+            response.version(1, state.firmware_version);
+            response.version(7, state.data_model_version);
+            response.version(13, state.usb_protocol_version);
+            response.version(19, state.slave_protocol_version);
+            response.version(25, state.module_protocol_version);
+        }
+        Ok(DevicePropertyIds::ConfigSizes) => {
+            response.u16(1, state.hardware_config.len() as u16);
+            response.u16(3, state.validated_user_config.len() as u16);
+        }
+        Ok(DevicePropertyIds::CurrentKbootCommand) => response.u8(1, 0),
+        Ok(DevicePropertyIds::I2cBaudRate) => {
+            response.u8(1, 0);
+            response.u32(2, 100_000);
+            response.u32(6, 100_000);
+        }
+        Ok(DevicePropertyIds::Uptime) => response.u32(1, state.booted.elapsed().as_millis() as u32),
+        Ok(DevicePropertyIds::GitTag) => response.string(1, &state.git_tag),
+        Ok(DevicePropertyIds::GitRepo) => response.string(1, &state.git_repo),
+        Err(_) => response.status(2),
+    }
+}
+
+fn get_module_property(state: &EmulatorState, slot: u8, property: u8, response: &mut Response) {
+    let present = match ModuleSlots::try_from(slot) {
+        Ok(ModuleSlots::LeftKeyboardHalf) => state.left_half_connected,
+        Ok(ModuleSlots::NoModule) | Err(_) => false,
+        Ok(module) => module == state.left_module_slot || module == state.right_module_slot,
+    };
+    if !present {
+        return response.status(UsbStatusCode::InvalidCommand);
+    }
+    match ModulePropertyId::try_from(property) {
+        Ok(ModulePropertyId::ProtocolVersions) => {
+            response.version(1, state.module_protocol_version);
+            response.version(7, state.firmware_version);
+        }
+        Ok(ModulePropertyId::GitTag) => response.string(1, &state.git_tag),
+        Ok(ModulePropertyId::GitRepo) => response.string(1, &state.git_repo),
+        Err(_) => response.status(2),
+    }
+}
+
+fn read_config(state: &EmulatorState, args: &[u8], response: &mut Response) {
+    let buffer = match ConfigBufferId::try_from(arg(args, 0)) {
+        Ok(ConfigBufferId::HardwareConfig) => &state.hardware_config,
+        Ok(ConfigBufferId::StagingUserConfig) => &state.staging_user_config,
+        Ok(ConfigBufferId::ValidatedUserConfig) => &state.validated_user_config,
+        Err(_) => return response.status(ReadConfigStatus::InvalidConfigBufferId),
+    };
+    let length = arg(args, 1) as usize;
+    let offset = u16::from_le_bytes([arg(args, 2), arg(args, 3)]) as usize;
+    if length > consts::MAX_PAYLOAD_SIZE - 1 {
+        return response.status(ReadConfigStatus::LengthTooLarge);
+    }
+    if offset + length > buffer.len() {
+        return response.status(ReadConfigStatus::BufferOutOfBounds);
+    }
+    response.buf[1..1 + length].copy_from_slice(&buffer[offset..offset + length]);
+}
+
+fn write_config(buffer: &mut [u8], args: &[u8], response: &mut Response) {
+    let length = arg(args, 0) as usize;
+    let offset = u16::from_le_bytes([arg(args, 1), arg(args, 2)]) as usize;
+    let data = args.get(3..).unwrap_or_default();
+    if length > consts::MAX_PAYLOAD_SIZE - 4 || length > data.len() {
+        return response.status(WriteConfigStatus::LengthTooLarge);
+    }
+    if offset + length > buffer.len() {
+        return response.status(WriteConfigStatus::BufferOutOfBounds);
+    }
+    buffer[offset..offset + length].copy_from_slice(&data[..length]);
+}
+
+fn apply_config(state: &mut EmulatorState, response: &mut Response) {
+    let mut cursor = UhkCursor::new(state.staging_user_config.clone());
+    match UserConfig::deserialize(&mut cursor) {
+        Ok(_) => {
+            state.validated_user_config = state.staging_user_config.clone();
+            state.active_layer = 0;
+            state.active_layer_toggled = false;
+        }
+        Err(_) => {
+            response.status(1);
+            response.u16(1, cursor.position() as u16);
+        }
+    }
+}
+
+fn launch_eeprom_transfer(state: &mut EmulatorState, args: &[u8], response: &mut Response) {
+    if state.eeprom_busy() {
+        return response.status(UsbStatusCode::InvalidCommand);
+    }
+    let operation = EepromOperation::try_from(arg(args, 0));
+    let buffer = ConfigBufferId::try_from(arg(args, 1));
+    match (operation, buffer) {
+        (Ok(EepromOperation::Write), Ok(ConfigBufferId::HardwareConfig)) => {
+            state.eeprom_hardware_config = state.hardware_config.clone()
+        }
+        (Ok(EepromOperation::Write), Ok(ConfigBufferId::ValidatedUserConfig)) => {
+            state.eeprom_user_config = state.validated_user_config.clone()
+        }
+        (Ok(EepromOperation::Read), Ok(ConfigBufferId::HardwareConfig)) => {
+            state.hardware_config = state.eeprom_hardware_config.clone()
+        }
+        (Ok(EepromOperation::Read), Ok(ConfigBufferId::StagingUserConfig)) => {
+            state.staging_user_config = state.eeprom_user_config.clone()
+        }
+        _ => return response.status(2),
+    }
+    state.eeprom_busy_until = Some(Instant::now() + state.eeprom_busy_time);
+}
+
+fn switch_keymap(state: &mut EmulatorState, args: &[u8], response: &mut Response) {
+    let length = arg(args, 0) as usize;
+    let abbr = match args.get(1..1 + length) {
+        Some(abbr) if (1..=3).contains(&length) => abbr,
+        _ => return response.status(SwitchKeymapStatus::InvalidAbbreviationLength),
+    };
+    // only keymaps of the validated config can be switched to
+    let config = UserConfig::deserialize(&mut UhkCursor::new(state.validated_user_config.clone()));
+    let known = config.is_ok_and(|config| {
+        config
+            .keymaps
+            .iter()
+            .any(|keymap| keymap.abbr.as_bytes() == abbr)
+    });
+    match String::from_utf8(abbr.to_vec()) {
+        Ok(abbr) if known => state.current_keymap = Some(abbr),
+        _ => response.status(SwitchKeymapStatus::InvalidAbbreviation),
+    }
+}
@@ -0,0 +1,252 @@
+//! Physical key positions of the UHK 60 (v1 and v2 share their geometry)
+//! and its add-on modules. Key ids number the keys of a module in reading
+//! order; ANSI and ISO halves use the same ids, the ANSI left half merely
+//! lacks the key next to the left shift.
+//!
+//! Columns and widths are in key units, columns measured from the left edge
+//! of the module.
+
+use crate::{config::HardwareConfig, consts::ModuleId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    pub id: u8,
+    pub row: u8,
+    pub column: f32,
+    pub width: f32,
+    /// Legend of the key on a US or UK keycap set.
+    pub label: &'static str,
+}
+
+const fn key(id: u8, row: u8, column: f32, width: f32, label: &'static str) -> Key {
+    Key {
+        id,
+        row,
+        column,
+        width,
+        label,
+    }
+}
+
+const RIGHT_HALF_ANSI: &[Key] = &[
+    key(0, 0, 0.5, 1.0, "7"),
+    key(1, 0, 1.5, 1.0, "8"),
+    key(2, 0, 2.5, 1.0, "9"),
+    key(3, 0, 3.5, 1.0, "0"),
+    key(4, 0, 4.5, 1.0, "-"),
+    key(5, 0, 5.5, 1.0, "="),
+    key(6, 0, 6.5, 2.0, "Bksp"),
+    key(7, 1, 0.0, 1.0, "Y"),
+    key(8, 1, 1.0, 1.0, "U"),
+    key(9, 1, 2.0, 1.0, "I"),
+    key(10, 1, 3.0, 1.0, "O"),
+    key(11, 1, 4.0, 1.0, "P"),
+    key(12, 1, 5.0, 1.0, "["),
+    key(13, 1, 6.0, 1.0, "]"),
+    key(14, 1, 7.0, 1.5, "\\"),
+    key(15, 2, 0.25, 1.0, "H"),
+    key(16, 2, 1.25, 1.0, "J"),
+    key(17, 2, 2.25, 1.0, "K"),
+    key(18, 2, 3.25, 1.0, "L"),
+    key(19, 2, 4.25, 1.0, ";"),
+    key(20, 2, 5.25, 1.0, "'"),
+    key(21, 2, 6.25, 2.25, "Enter"),
+    key(22, 3, 0.75, 1.0, "N"),
+    key(23, 3, 1.75, 1.0, "M"),
+    key(24, 3, 2.75, 1.0, ","),
+    key(25, 3, 3.75, 1.0, "."),
+    key(26, 3, 4.75, 1.0, "/"),
+    key(27, 3, 5.75, 2.75, "Shift"),
+    key(28, 4, 0.0, 2.25, "Space"),
+    key(29, 4, 2.25, 1.25, "Mod"),
+    key(30, 4, 3.5, 1.25, "Fn"),
+    key(31, 4, 4.75, 1.25, "Alt"),
+    key(32, 4, 6.0, 1.25, "Super"),
+    key(33, 4, 7.25, 1.25, "Ctrl"),
+];
+
+/// Like ANSI, except that the enter key sits at the end of the second row
+/// and a hash key takes its place on the third.
+const RIGHT_HALF_ISO: &[Key] = &[
+    key(0, 0, 0.5, 1.0, "7"),
+    key(1, 0, 1.5, 1.0, "8"),
+    key(2, 0, 2.5, 1.0, "9"),
+    key(3, 0, 3.5, 1.0, "0"),
+    key(4, 0, 4.5, 1.0, "-"),
+    key(5, 0, 5.5, 1.0, "="),
+    key(6, 0, 6.5, 2.0, "Bksp"),
+    key(7, 1, 0.0, 1.0, "Y"),
+    key(8, 1, 1.0, 1.0, "U"),
+    key(9, 1, 2.0, 1.0, "I"),
+    key(10, 1, 3.0, 1.0, "O"),
+    key(11, 1, 4.0, 1.0, "P"),
+    key(12, 1, 5.0, 1.0, "["),
+    key(13, 1, 6.0, 1.0, "]"),
+    key(14, 1, 7.0, 1.5, "Enter"),
+    key(15, 2, 0.25, 1.0, "H"),
+    key(16, 2, 1.25, 1.0, "J"),
+    key(17, 2, 2.25, 1.0, "K"),
+    key(18, 2, 3.25, 1.0, "L"),
+    key(19, 2, 4.25, 1.0, ";"),
+    key(20, 2, 5.25, 1.0, "'"),
+    key(21, 2, 6.25, 1.0, "#"),
+    key(22, 3, 0.75, 1.0, "N"),
+    key(23, 3, 1.75, 1.0, "M"),
+    key(24, 3, 2.75, 1.0, ","),
+    key(25, 3, 3.75, 1.0, "."),
+    key(26, 3, 4.75, 1.0, "/"),
+    key(27, 3, 5.75, 2.75, "Shift"),
+    key(28, 4, 0.0, 2.25, "Space"),
+    key(29, 4, 2.25, 1.25, "Mod"),
+    key(30, 4, 3.5, 1.25, "Fn"),
+    key(31, 4, 4.75, 1.25, "Alt"),
+    key(32, 4, 6.0, 1.25, "Super"),
+    key(33, 4, 7.25, 1.25, "Ctrl"),
+];
+
+const LEFT_HALF_ANSI: &[Key] = &[
+    key(0, 0, 0.0, 1.0, "`"),
+    key(1, 0, 1.0, 1.0, "1"),
+    key(2, 0, 2.0, 1.0, "2"),
+    key(3, 0, 3.0, 1.0, "3"),
+    key(4, 0, 4.0, 1.0, "4"),
+    key(5, 0, 5.0, 1.0, "5"),
+    key(6, 0, 6.0, 1.0, "6"),
+    key(7, 1, 0.0, 1.5, "Tab"),
+    key(8, 1, 1.5, 1.0, "Q"),
+    key(9, 1, 2.5, 1.0, "W"),
+    key(10, 1, 3.5, 1.0, "E"),
+    key(11, 1, 4.5, 1.0, "R"),
+    key(12, 1, 5.5, 1.0, "T"),
+    key(13, 2, 0.0, 1.75, "Mouse"),
+    key(14, 2, 1.75, 1.0, "A"),
+    key(15, 2, 2.75, 1.0, "S"),
+    key(16, 2, 3.75, 1.0, "D"),
+    key(17, 2, 4.75, 1.0, "F"),
+    key(18, 2, 5.75, 1.0, "G"),
+    key(19, 3, 0.0, 2.25, "Shift"),
+    key(21, 3, 2.25, 1.0, "Z"),
+    key(22, 3, 3.25, 1.0, "X"),
+    key(23, 3, 4.25, 1.0, "C"),
+    key(24, 3, 5.25, 1.0, "V"),
+    key(25, 3, 6.25, 1.0, "B"),
+    key(26, 4, 0.0, 1.25, "Ctrl"),
+    key(27, 4, 1.25, 1.25, "Super"),
+    key(28, 4, 2.5, 1.25, "Alt"),
+    key(29, 4, 3.75, 1.25, "Fn"),
+    key(30, 4, 5.0, 1.25, "Mod"),
+    key(31, 4, 6.25, 1.0, "Space"),
+];
+
+/// Like ANSI, with a shorter left shift making room for key 20.
+const LEFT_HALF_ISO: &[Key] = &[
+    key(0, 0, 0.0, 1.0, "`"),
+    key(1, 0, 1.0, 1.0, "1"),
+    key(2, 0, 2.0, 1.0, "2"),
+    key(3, 0, 3.0, 1.0, "3"),
+    key(4, 0, 4.0, 1.0, "4"),
+    key(5, 0, 5.0, 1.0, "5"),
+    key(6, 0, 6.0, 1.0, "6"),
+    key(7, 1, 0.0, 1.5, "Tab"),
+    key(8, 1, 1.5, 1.0, "Q"),
+    key(9, 1, 2.5, 1.0, "W"),
+    key(10, 1, 3.5, 1.0, "E"),
+    key(11, 1, 4.5, 1.0, "R"),
+    key(12, 1, 5.5, 1.0, "T"),
+    key(13, 2, 0.0, 1.75, "Mouse"),
+    key(14, 2, 1.75, 1.0, "A"),
+    key(15, 2, 2.75, 1.0, "S"),
+    key(16, 2, 3.75, 1.0, "D"),
+    key(17, 2, 4.75, 1.0, "F"),
+    key(18, 2, 5.75, 1.0, "G"),
+    key(19, 3, 0.0, 1.25, "Shift"),
+    key(20, 3, 1.25, 1.0, "\\"),
+    key(21, 3, 2.25, 1.0, "Z"),
+    key(22, 3, 3.25, 1.0, "X"),
+    key(23, 3, 4.25, 1.0, "C"),
+    key(24, 3, 5.25, 1.0, "V"),
+    key(25, 3, 6.25, 1.0, "B"),
+    key(26, 4, 0.0, 1.25, "Ctrl"),
+    key(27, 4, 1.25, 1.25, "Super"),
+    key(28, 4, 2.5, 1.25, "Alt"),
+    key(29, 4, 3.75, 1.25, "Fn"),
+    key(30, 4, 5.0, 1.25, "Mod"),
+    key(31, 4, 6.25, 1.0, "Space"),
+];
+
+const KEY_CLUSTER: &[Key] = &[
+    key(0, 0, 0.0, 1.0, "Left"),
+    key(1, 0, 1.0, 1.0, "Middle"),
+    key(2, 0, 2.0, 1.0, "Right"),
+];
+
+const TRACKBALL: &[Key] = &[key(0, 0, 0.0, 1.0, "Left"), key(1, 0, 1.0, 1.0, "Right")];
+
+const TRACKPOINT: &[Key] = &[key(0, 0, 0.0, 1.0, "Left"), key(1, 0, 1.0, 1.0, "Right")];
+
+/// Key geometry for one keyboard, selected by the layout of its halves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub iso: bool,
+}
+
+impl Layout {
+    pub fn new(hardware: &HardwareConfig) -> Self {
+        Self {
+            iso: hardware.iso_mode,
+        }
+    }
+
+    /// Keys of a module in id order; empty for modules without keys.
+    pub fn keys(&self, module: u8) -> &'static [Key] {
+        match ModuleId::try_from(module) {
+            Ok(ModuleId::RightKeyboardHalf) if self.iso => RIGHT_HALF_ISO,
+            Ok(ModuleId::RightKeyboardHalf) => RIGHT_HALF_ANSI,
+            Ok(ModuleId::LeftKeyboardHalf) if self.iso => LEFT_HALF_ISO,
+            Ok(ModuleId::LeftKeyboardHalf) => LEFT_HALF_ANSI,
+            Ok(ModuleId::KeyClusterLeft) => KEY_CLUSTER,
+            Ok(ModuleId::TrackballRight) => TRACKBALL,
+            Ok(ModuleId::TrackpointRight) => TRACKPOINT,
+            Ok(ModuleId::TouchpadRight) | Err(_) => &[],
+        }
+    }
+
+    pub fn key(&self, module: u8, id: u8) -> Option<&'static Key> {
+        self.keys(module).iter().find(|key| key.id == id)
+    }
+
+    /// Finds a key by its label, ignoring case.
+    pub fn find(&self, module: u8, label: &str) -> Option<&'static Key> {
+        self.keys(module)
+            .iter()
+            .find(|key| key.label.eq_ignore_ascii_case(label))
+    }
+
+    /// Width of a module in key units.
+    pub fn width(&self, module: u8) -> f32 {
+        self.keys(module)
+            .iter()
+            .map(|key| key.column + key.width)
+            .fold(0.0, f32::max)
+    }
+
+    /// Number of rows of a module.
+    pub fn row_count(&self, module: u8) -> usize {
+        self.keys(module)
+            .iter()
+            .map(|key| key.row as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Number of key ids in each row of a module, counting keys that only exist
+/// in one of the layouts.
+pub fn rows(module: u8) -> Vec<usize> {
+    let iso = Layout { iso: true };
+    let mut rows = vec![0; iso.row_count(module)];
+    for key in iso.keys(module) {
+        rows[key.row as usize] += 1;
+    }
+    rows
+}
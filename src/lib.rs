@@ -1,10 +1,25 @@
 #![feature(iterator_try_collect)]
 use hidapi::{DeviceInfo, HidApi};
 
+#[cfg(feature = "serde")]
+pub mod agent;
+pub mod capture;
 pub mod config;
 pub mod consts;
 pub mod device;
+pub mod emulator;
+pub mod layout;
+pub mod migrate;
 pub mod models;
+#[cfg(feature = "serde")]
+mod names;
+pub mod pcapng;
+pub mod render;
+pub mod scancodes;
+pub mod svg;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod transport;
 
 pub fn devices(api: &HidApi) -> Vec<&DeviceInfo> {
     let devices = api
@@ -1,114 +1,546 @@
-#![feature(cstr_from_bytes_until_nul)]
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
 use hidapi::HidApi;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 use uhkctl::{
+    agent,
+    capture::{read_capture, Recorder, Replay},
     config::{HardwareConfig, KeymapConfig, UserConfig},
-    consts::{
-        MacroActionId, ModuleSlots, UsbVariables,
-    },
-    device::{Device, UhkCursor},
+    consts::{ConfigBufferId, ModulePropertyId, ModuleSlots, UsbVariables, LAYER_NUMBER_TO_STRING},
+    device::{Device, DeviceError, UhkCursor, UhkWriter, Version},
+    layout::Layout,
+    migrate, pcapng,
+    render::{self, Style},
+    svg, text,
+    transport::Transport,
 };
 
-fn main() -> Result<()> {
+const EXIT_FAILURE: u8 = 1;
+const EXIT_NO_DEVICE: u8 = 3;
+const EXIT_PROTOCOL: u8 = 4;
+
+#[derive(Parser)]
+#[command(version, about = "Control Ultimate Hacking Keyboards")]
+struct Cli {
+    /// HID path or serial number of the keyboard to use
+    #[arg(long, global = true)]
+    device: Option<String>,
+    /// Log every HID report exchanged with the keyboard to a capture file
+    #[arg(long, global = true, conflicts_with = "replay")]
+    capture: Option<PathBuf>,
+    /// Serve responses from a capture file instead of a keyboard
+    #[arg(long, global = true)]
+    replay: Option<PathBuf>,
+    /// Print query results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List connected keyboards
+    List,
+    /// Show hardware configuration and firmware information
+    Info,
+    /// Show the current device state
+    State,
+    /// Print the validated user configuration
+    DumpConfig,
+    /// Save the validated user configuration to a file
+    Backup {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Binary)]
+        format: Format,
+    },
+    /// Upload a user configuration from a file
+    Restore {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Binary)]
+        format: Format,
+    },
+    /// Draw the layers of a keymap
+    Show {
+        keymap: String,
+        /// Only draw the layer with this name or number
+        #[arg(long)]
+        layer: Option<String>,
+        /// Draw key borders with ASCII characters only
+        #[arg(long)]
+        ascii: bool,
+    },
+    /// Write an SVG cheat sheet of all layers of a keymap
+    Cheatsheet { keymap: String, output: PathBuf },
+    /// Switch to the keymap with the given abbreviation
+    SwitchKeymap { abbr: String },
+    /// Read a USB variable
+    GetVar { var: Variable },
+    /// Write a USB variable
+    SetVar { var: Variable, value: u8 },
+    /// Execute a macro command
+    ExecMacro { command: String },
+    /// Convert a user configuration file between formats without a keyboard
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Format::Binary)]
+        from: Format,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        to: Format,
+    },
+    /// Convert a user configuration file to another data model version
+    Migrate {
+        input: PathBuf,
+        /// Where to write the result, which must not be the input
+        output: PathBuf,
+        /// Data model version to convert to, such as 5.0
+        #[arg(long, value_parser = parse_version)]
+        to: Version,
+        #[arg(long, value_enum, default_value_t = Format::Binary)]
+        format: Format,
+    },
+    /// Convert a capture file to pcapng for Wireshark
+    ConvertCapture { input: PathBuf, output: PathBuf },
+}
+
+/// On-disk representation of a user configuration
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Raw configuration bytes as stored in the keyboard
+    Binary,
+    /// The user-config.json document used by Agent
+    AgentJson,
+    /// TOML with one key grid per layer and module
+    Text,
+}
+
+#[derive(Clone, Copy, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Variable {
+    TestSwitches,
+    TestUsbStack,
+    DebounceTimePress,
+    DebounceTimeRelease,
+    UsbReportSemaphore,
+}
+
+impl From<Variable> for UsbVariables {
+    fn from(var: Variable) -> Self {
+        match var {
+            Variable::TestSwitches => Self::TestSwitches,
+            Variable::TestUsbStack => Self::TestUsbStack,
+            Variable::DebounceTimePress => Self::DebounceTimePress,
+            Variable::DebounceTimeRelease => Self::DebounceTimeRelease,
+            Variable::UsbReportSemaphore => Self::UsbReportSemaphore,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("no matching keyboard found")]
+struct NoDevice;
+
+fn main() -> ExitCode {
     env_logger::init();
-    let api = HidApi::new()?;
-    let devices = uhkctl::devices(&api);
-    let info = devices.first().unwrap();
-    let device = info.open_device(&api).unwrap();
-    let device = Device::open(device);
-    dbg!(device.state().unwrap());
-    dbg!(device.uptime().unwrap());
-    dbg!(device.get_variable(UsbVariables::TestSwitches).unwrap());
-    dbg!(device.get_variable(UsbVariables::TestUsbStack).unwrap());
-    dbg!(device
-        .get_variable(UsbVariables::DebounceTimePress)
-        .unwrap());
-    dbg!(device
-        .get_variable(UsbVariables::DebounceTimeRelease)
-        .unwrap());
-    dbg!(device
-        .get_variable(UsbVariables::UsbReportSemaphore)
-        .unwrap());
-    dbg!(device.get_config_size().unwrap());
-    let p = device
-        .get_module_property(
-            ModuleSlots::LeftKeyboardHalf,
-            uhkctl::consts::ModulePropertyId::GitTag,
-        )
-        .unwrap();
-    dbg!(std::ffi::CStr::from_bytes_until_nul(&p[1..]).unwrap());
-    let p = device
-        .get_module_property(
-            ModuleSlots::LeftKeyboardHalf,
-            uhkctl::consts::ModulePropertyId::GitRepo,
-        )
-        .unwrap();
-    dbg!(std::ffi::CStr::from_bytes_until_nul(&p[1..]).unwrap());
-    let cfg = device
-        .load_config(uhkctl::consts::ConfigBufferId::HardwareConfig)
-        .unwrap();
-    let mut cursor = UhkCursor::new(cfg);
-    dbg!(HardwareConfig::deserialize(&mut cursor).unwrap());
-
-    let cfg = device
-        .load_config(uhkctl::consts::ConfigBufferId::ValidatedUserConfig)
-        .unwrap();
-    let mut cursor = UhkCursor::new(cfg);
-    dbg!(UserConfig::deserialize(&mut cursor).unwrap());
-
-    let num_modules = cursor.read_compact_length().unwrap();
-    dbg!(num_modules);
-    for i in 0..num_modules {
-        dbg!(i);
-        dbg!(cursor.read_u8().unwrap()); // id
-        dbg!(cursor.read_u8().unwrap()); // pointerMode
-        dbg!(cursor.read_u8().unwrap()); // deceleratedPointerSpeedMultiplier
-        dbg!(cursor.read_u8().unwrap()); // basePointerSpeedMultiplier
-        dbg!(cursor.read_u8().unwrap()); // acceleratedPointerSpeedMultiplier
-        dbg!(cursor.read_u16().unwrap()); // angularShift
-        dbg!(cursor.read_u8().unwrap()); // modLayerPointerFunction
-        dbg!(cursor.read_u8().unwrap()); // fnLayerPointerFunction
-        dbg!(cursor.read_u8().unwrap()); // mouseLayerPointerFunction
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            ExitCode::from(if err.is::<NoDevice>() {
+                EXIT_NO_DEVICE
+            } else if err.is::<DeviceError>() {
+                EXIT_PROTOCOL
+            } else {
+                EXIT_FAILURE
+            })
+        }
     }
+}
 
-    let num_macros = cursor.read_compact_length().unwrap();
-    dbg!(num_macros);
-    for i in 0..num_macros {
-        dbg!(i);
-        dbg!(cursor.read_bool().unwrap()); // is looped
-        dbg!(cursor.read_bool().unwrap()); // is private
-        dbg!(cursor.read_string().unwrap()); // name
-        let action_length = cursor.read_compact_length().unwrap();
-        dbg!(action_length);
-        for _j in 0..action_length {
-            let id = cursor.read_u8().unwrap();
-            dbg!(id);
-            match id {
-                _ if id >= MacroActionId::KeyMacroAction.into()
-                    && id <= MacroActionId::LastKeyMacroAction.into() =>
-                {
-                    unimplemented!()
+fn run(cli: Cli) -> Result<()> {
+    if let Command::List = cli.command {
+        let api = HidApi::new()?;
+        let devices = uhkctl::devices(&api);
+        if cli.json {
+            let devices: Vec<_> = devices
+                .iter()
+                .map(|info| {
+                    json!({
+                        "path": info.path().to_string_lossy(),
+                        "serial_number": info.serial_number(),
+                        "product": info.product_string(),
+                    })
+                })
+                .collect();
+            return print_json(&devices);
+        }
+        for info in devices {
+            println!(
+                "{}\t{}\t{}",
+                info.path().to_string_lossy(),
+                info.serial_number().unwrap_or("-"),
+                info.product_string().unwrap_or("-"),
+            );
+        }
+        return Ok(());
+    }
+    if let Command::Convert {
+        input,
+        output,
+        from,
+        to,
+    } = &cli.command
+    {
+        let (config, _) = read_user_config(input, *from)?;
+        let data = encode_user_config(&config, *to)?;
+        return std::fs::write(output, data)
+            .with_context(|| format!("writing {}", output.display()));
+    }
+    if let Command::Migrate {
+        input,
+        output,
+        to,
+        format,
+    } = &cli.command
+    {
+        // a lossy downgrade must leave the original in place
+        if output.canonicalize().ok() == Some(input.canonicalize()?) {
+            return Err(anyhow!("refusing to overwrite {}", input.display()));
+        }
+        let (mut config, _) = read_user_config(input, *format)?;
+        for warning in migrate::migrate(&mut config, *to)? {
+            eprintln!("warning: {}", warning);
+        }
+        let data = encode_user_config(&config, *format)?;
+        return std::fs::write(output, data)
+            .with_context(|| format!("writing {}", output.display()));
+    }
+    if let Command::ConvertCapture { input, output } = &cli.command {
+        let file = File::open(input).with_context(|| format!("opening {}", input.display()))?;
+        let records = read_capture(BufReader::new(file))
+            .with_context(|| format!("reading {}", input.display()))?;
+        let file =
+            File::create(output).with_context(|| format!("creating {}", output.display()))?;
+        let mut out = BufWriter::new(file);
+        pcapng::write_pcapng(&records, &mut out)
+            .and_then(|()| out.flush())
+            .with_context(|| format!("writing {}", output.display()))?;
+        return Ok(());
+    }
+    let device = Device::open(open_transport(&cli)?);
+    match cli.command {
+        Command::List
+        | Command::Convert { .. }
+        | Command::Migrate { .. }
+        | Command::ConvertCapture { .. } => unreachable!(),
+        Command::Info => {
+            let hardware = load_hardware_config(&device)?;
+            let uptime = device.uptime()?;
+            let device_protocol = device.device_protocol_version()?;
+            let versions = device.protocol_versions()?;
+            let i2c_baud_rate = device.i2c_baud_rate()?;
+            let right_half = [
+                ("git_tag", device.git_tag()?),
+                ("git_repo", device.git_repo()?),
+            ];
+            // a disconnected left half does not answer module queries
+            let mut left_half = None;
+            if device.state()?.left_half_connected {
+                let mut properties = vec![];
+                for (name, property) in [
+                    ("git_tag", ModulePropertyId::GitTag),
+                    ("git_repo", ModulePropertyId::GitRepo),
+                ] {
+                    let value = device
+                        .get_module_property(ModuleSlots::LeftKeyboardHalf, property)?
+                        .read_nul_terminated()?;
+                    properties.push((name, value));
                 }
-                _ if id >= MacroActionId::MouseButtonMacroAction.into()
-                    && id <= MacroActionId::LastMouseButtonMacroAction.into() =>
-                {
-                    unimplemented!()
+                left_half = Some(properties);
+            }
+            if cli.json {
+                return print_json(&json!({
+                    "hardware_config": hardware,
+                    "uptime_ms": uptime.as_millis() as u64,
+                    "device_protocol_version": device_protocol,
+                    "protocol_versions": versions,
+                    "i2c_baud_rate": i2c_baud_rate,
+                    "right_half": right_half.iter().cloned().collect::<std::collections::BTreeMap<_, _>>(),
+                    "left_half": left_half.as_ref().map(|properties| properties.iter().cloned().collect::<std::collections::BTreeMap<_, _>>()),
+                }));
+            }
+            println!("signature: {}", hardware.signature);
+            println!(
+                "hardware config: {}.{}.{}",
+                hardware.major, hardware.minor, hardware.patch
+            );
+            println!("brand id: {}", hardware.brand_id);
+            println!("device id: {}", hardware.device_id);
+            println!("unique id: {:#010x}", hardware.unique_id);
+            println!("vendor mode: {}", hardware.vendor_mode);
+            println!("iso mode: {}", hardware.iso_mode);
+            println!("uptime: {:?}", uptime);
+            println!("device protocol: {}", device_protocol);
+            println!("firmware: {}", versions.firmware);
+            println!("data model: {}", versions.data_model);
+            println!("usb protocol: {}", versions.usb_protocol);
+            println!("slave protocol: {}", versions.slave_protocol);
+            println!("module protocol: {}", versions.module_protocol);
+            println!(
+                "i2c baud rate: {} (requested {})",
+                i2c_baud_rate.actual, i2c_baud_rate.requested
+            );
+            for (name, value) in right_half {
+                println!("right half {}: {}", name.replace('_', " "), value);
+            }
+            match left_half {
+                Some(properties) => {
+                    for (name, value) in properties {
+                        println!("left half {}: {}", name.replace('_', " "), value);
+                    }
                 }
-                _ if id == MacroActionId::CommandMacroAction.into() => {
-                    dbg!(cursor.read_string().unwrap()); // command
+                None => println!("left half: not connected"),
+            }
+        }
+        Command::State => {
+            let state = device.state()?;
+            if cli.json {
+                return print_json(&state);
+            }
+            println!("eeprom busy: {}", state.eeprom_busy);
+            println!("halves merged: {}", state.halves_merged);
+            println!("left half connected: {}", state.left_half_connected);
+            println!("left module: {:?}", state.left_module_slot);
+            println!("right module: {:?}", state.right_module_slot);
+            println!(
+                "active layer: {}{}",
+                state.active_layer,
+                if state.active_layer_toggled {
+                    " (toggled)"
+                } else {
+                    ""
                 }
-                _ => {
-                    unimplemented!()
+            );
+        }
+        Command::DumpConfig => {
+            let (config, _) = load_user_config(&device)?;
+            if cli.json {
+                return print_json(&config);
+            }
+            print_user_config(&config);
+        }
+        Command::Backup { file, format } => {
+            // binary backups work for data models uhkctl cannot parse
+            let data = match format {
+                Format::Binary => load_user_config_data(&device)?,
+                _ => encode_user_config(&load_user_config(&device)?.0, format)?,
+            };
+            std::fs::write(&file, data).with_context(|| format!("writing {}", file.display()))?;
+        }
+        Command::Restore { file, format } => {
+            let (_, data) = read_user_config(&file, format)?;
+            device.upload_user_config(&data)?;
+        }
+        Command::Show {
+            keymap,
+            layer,
+            ascii,
+        } => {
+            let layout = Layout::new(&load_hardware_config(&device)?);
+            let (config, _) = load_user_config(&device)?;
+            let keymap = find_keymap(&config, &keymap)?;
+            let style = if ascii { Style::Ascii } else { Style::Unicode };
+            match layer {
+                Some(name) => {
+                    let id = LAYER_NUMBER_TO_STRING
+                        .iter()
+                        .position(|layer| *layer == name)
+                        .map(|id| id as u8)
+                        .or_else(|| name.parse().ok())
+                        .ok_or_else(|| anyhow!("unknown layer {:?}", name))?;
+                    let layer = keymap
+                        .layers
+                        .iter()
+                        .find(|layer| layer.id == id)
+                        .ok_or_else(|| anyhow!("keymap {} has no {} layer", keymap.abbr, name))?;
+                    print!("{}", render::render_layer(&config, layer, &layout, style));
                 }
+                None => print!("{}", render::render_keymap(&config, keymap, &layout, style)),
+            }
+        }
+        Command::Cheatsheet { keymap, output } => {
+            let layout = Layout::new(&load_hardware_config(&device)?);
+            let (config, _) = load_user_config(&device)?;
+            let keymap = find_keymap(&config, &keymap)?;
+            std::fs::write(&output, svg::render_keymap(&config, keymap, &layout))
+                .with_context(|| format!("writing {}", output.display()))?;
+        }
+        Command::SwitchKeymap { abbr } => device.switch_keymap(&abbr)?,
+        Command::GetVar { var } => {
+            let value = device.get_variable(var.into())?;
+            if cli.json {
+                return print_json(&json!({ "variable": var, "value": value }));
             }
+            println!("{}", value);
         }
+        Command::SetVar { var, value } => device.set_variable(var.into(), value)?,
+        Command::ExecMacro { command } => device.exec_macro_command(&command)?,
     }
+    Ok(())
+}
 
-    let num_keymaps = cursor.read_compact_length().unwrap();
-    dbg!(num_keymaps);
-    for _ in 0..num_keymaps {
-        dbg!(KeymapConfig::deserialize(&mut cursor).unwrap());
+fn open_transport(cli: &Cli) -> Result<Box<dyn Transport>> {
+    if let Some(path) = &cli.replay {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        return Ok(Box::new(Replay::from_reader(BufReader::new(file))?));
+    }
+    let api = HidApi::new()?;
+    let devices = uhkctl::devices(&api);
+    let info = devices
+        .into_iter()
+        .find(|info| match &cli.device {
+            Some(device) => {
+                info.path().to_string_lossy() == *device
+                    || info.serial_number() == Some(device.as_str())
+            }
+            None => true,
+        })
+        .ok_or(NoDevice)?;
+    let dev = info.open_device(&api)?;
+    match &cli.capture {
+        Some(path) => {
+            let file =
+                File::create(path).with_context(|| format!("creating {}", path.display()))?;
+            Ok(Box::new(Recorder::new(dev, file)?))
+        }
+        None => Ok(Box::new(dev)),
     }
+}
 
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
     Ok(())
 }
+
+fn load_hardware_config<T: Transport>(device: &Device<T>) -> Result<HardwareConfig> {
+    let data = device.load_config(ConfigBufferId::HardwareConfig)?;
+    Ok(HardwareConfig::deserialize(&mut UhkCursor::new(data))?)
+}
+
+/// Loads the validated user config, returning it parsed and as raw bytes
+/// trimmed to its serialized length.
+fn load_user_config<T: Transport>(device: &Device<T>) -> Result<(UserConfig, Vec<u8>)> {
+    device.check_data_model()?;
+    let data = load_user_config_data(device)?;
+    let config = UserConfig::deserialize(&mut UhkCursor::new(data.clone()))?;
+    Ok((config, data))
+}
+
+/// Loads the raw validated user config, trimmed to the length its header
+/// gives without parsing the rest.
+fn load_user_config_data<T: Transport>(device: &Device<T>) -> Result<Vec<u8>> {
+    let mut data = device.load_config(ConfigBufferId::ValidatedUserConfig)?;
+    // the length follows the data model version
+    let mut cursor = UhkCursor::new(data.clone());
+    Version::deserialize(&mut cursor)?;
+    let length = cursor.read_u16()? as usize;
+    if length > data.len() {
+        return Err(anyhow!("config length {} exceeds buffer", length));
+    }
+    data.truncate(length);
+    Ok(data)
+}
+
+/// Parses versions such as `5`, `5.0` or `5.0.1`, missing parts being zero.
+fn parse_version(version: &str) -> Result<Version> {
+    let parts = version
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<Vec<u16>, _>>()?;
+    match parts[..] {
+        [major] => Ok(Version::new(major, 0, 0)),
+        [major, minor] => Ok(Version::new(major, minor, 0)),
+        [major, minor, patch] => Ok(Version::new(major, minor, patch)),
+        _ => Err(anyhow!("expected a version such as 5.0")),
+    }
+}
+
+fn find_keymap<'a>(config: &'a UserConfig, abbr: &str) -> Result<&'a KeymapConfig> {
+    config
+        .keymaps
+        .iter()
+        .find(|keymap| keymap.abbr == abbr)
+        .ok_or_else(|| anyhow!("no keymap {:?}", abbr))
+}
+
+/// Reads a user config file, returning it parsed and in its binary form.
+fn read_user_config(file: &Path, format: Format) -> Result<(UserConfig, Vec<u8>)> {
+    let read = || -> Result<(UserConfig, Vec<u8>)> {
+        let config = match format {
+            Format::Binary => {
+                let data = std::fs::read(file)?;
+                let config = UserConfig::deserialize(&mut UhkCursor::new(data.clone()))?;
+                return Ok((config, data));
+            }
+            Format::AgentJson => agent::from_json(&std::fs::read_to_string(file)?)?,
+            Format::Text => text::compile_file(file)?,
+        };
+        let data = encode_user_config(&config, Format::Binary)?;
+        Ok((config, data))
+    };
+    read().with_context(|| format!("reading {}", file.display()))
+}
+
+fn encode_user_config(config: &UserConfig, format: Format) -> Result<Vec<u8>> {
+    Ok(match format {
+        Format::Binary => {
+            let mut writer = UhkWriter::new();
+            config.serialize(&mut writer)?;
+            writer.into_inner()
+        }
+        Format::AgentJson => agent::to_json(config)?.into_bytes(),
+        Format::Text => text::decompile(config)?.into_bytes(),
+    })
+}
+
+fn print_user_config(config: &UserConfig) {
+    println!("name: {}", config.name);
+    println!(
+        "data model: {}.{}.{}",
+        config.major, config.minor, config.patch
+    );
+    for (i, macro_) in config.macros.iter().enumerate() {
+        println!(
+            "macro {}: {} ({} actions)",
+            i,
+            macro_.name,
+            macro_.actions.len()
+        );
+    }
+    for keymap in &config.keymaps {
+        println!(
+            "keymap {}: {}{}",
+            keymap.abbr,
+            keymap.name,
+            if keymap.default { " (default)" } else { "" }
+        );
+        for layer in &keymap.layers {
+            println!("  layer {}", layer.id);
+            for module in &layer.modules {
+                println!("    module {}", module.id);
+                for (key, action) in module.key_actions.iter().enumerate() {
+                    println!("      {:2}: {}", key, action);
+                }
+            }
+        }
+    }
+}
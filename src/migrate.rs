@@ -0,0 +1,120 @@
+//! Moving user configs between data model versions. Upgrading only fills in
+//! defaults for the settings a newer layout adds; downgrading drops what the
+//! older layout cannot store, reporting every change that alters behavior.
+
+use crate::{
+    config::{Backlighting, DataModel, KeyAction, ModuleConfiguration, UserConfig},
+    consts::LAYER_NUMBER_TO_STRING,
+    device::{ConfigPath, DeviceError, DeviceResult, PathSegment, Version},
+};
+use std::fmt;
+
+/// A setting lost when downgrading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// Backlighting mode and color were reset to functional backlighting.
+    Backlighting,
+    /// Axis locking of a pointing module was turned off.
+    AxisLock { module: u8 },
+    /// A layer with assigned keys was removed from its keymap.
+    Layer { path: ConfigPath, layer: u8 },
+    /// A key switching to a removed layer was unassigned.
+    SwitchLayer { path: ConfigPath, layer: u8 },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Backlighting => write!(f, "backlighting settings dropped"),
+            Self::AxisLock { module } => write!(f, "axis locking of module {} dropped", module),
+            Self::Layer { path, layer } => write!(f, "{}: {} layer dropped", path, name(*layer)),
+            Self::SwitchLayer { path, layer } => {
+                write!(f, "{}: switch to {} layer unassigned", path, name(*layer))
+            }
+        }
+    }
+}
+
+fn name(layer: u8) -> String {
+    match LAYER_NUMBER_TO_STRING.get(layer as usize) {
+        Some(name) => name.to_string(),
+        None => layer.to_string(),
+    }
+}
+
+/// Converts a config to the layout of another data model version, returning
+/// what had to be dropped on the way.
+pub fn migrate(config: &mut UserConfig, to: Version) -> DeviceResult<Vec<Warning>> {
+    if !UserConfig::supports(to) {
+        return Err(DeviceError::UnsupportedDataModel(to));
+    }
+    let model = DataModel::of(to).ok_or(DeviceError::UnsupportedDataModel(to))?;
+    let from = config.version();
+    let mut warnings = vec![];
+    if DataModel::of(from).is_some_and(|from| from > model) {
+        downgrade(config, model, &mut warnings);
+    }
+    config.major = to.major;
+    config.minor = to.minor;
+    config.patch = to.patch;
+    Ok(warnings)
+}
+
+fn downgrade(config: &mut UserConfig, model: DataModel, warnings: &mut Vec<Warning>) {
+    if model < DataModel::V6 {
+        if config.backlighting != Backlighting::default() {
+            config.backlighting = Backlighting::default();
+            warnings.push(Warning::Backlighting);
+        }
+        for module in &mut config.module_configurations {
+            drop_axis_lock(module, warnings);
+        }
+    }
+    // paths refer to the config as it was before removing layers
+    let count = model.layer_count();
+    for (i, keymap) in config.keymaps.iter_mut().enumerate() {
+        for (j, layer) in keymap.layers.iter_mut().enumerate() {
+            let path = |segments: &[PathSegment]| {
+                let mut path = vec![PathSegment::Keymap(i), PathSegment::Layer(j)];
+                path.extend_from_slice(segments);
+                ConfigPath(path)
+            };
+            if layer.id as usize >= count {
+                let assigned = layer
+                    .modules
+                    .iter()
+                    .flat_map(|module| &module.key_actions)
+                    .any(|action| !matches!(action, KeyAction::None));
+                if assigned {
+                    warnings.push(Warning::Layer {
+                        path: path(&[]),
+                        layer: layer.id,
+                    });
+                }
+                continue;
+            }
+            for (k, module) in layer.modules.iter_mut().enumerate() {
+                for (l, action) in module.key_actions.iter_mut().enumerate() {
+                    if let KeyAction::SwitchLayer(target, _) = *action {
+                        if target as usize >= count {
+                            *action = KeyAction::None;
+                            warnings.push(Warning::SwitchLayer {
+                                path: path(&[PathSegment::Module(k), PathSegment::Key(l)]),
+                                layer: target,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        keymap.layers.retain(|layer| (layer.id as usize) < count);
+    }
+}
+
+fn drop_axis_lock(module: &mut ModuleConfiguration, warnings: &mut Vec<Warning>) {
+    if module.cursor_axis_lock || module.scroll_axis_lock {
+        module.cursor_axis_lock = false;
+        module.scroll_axis_lock = false;
+        warnings.push(Warning::AxisLock { module: module.id });
+    }
+}
@@ -0,0 +1,109 @@
+//! Names of enum values shared by the Agent document and the text format,
+//! in the camelCase spelling Agent uses.
+
+use crate::consts::{
+    BacklightingMode, KeystrokeType, MacroSubAction, MouseActionParam, PointerFunction,
+    PointerMode, SecondaryRole, SwitchLayerMode, LAYER_NUMBER_TO_STRING,
+};
+
+pub(crate) const KEYSTROKE_TYPES: &[(KeystrokeType, &str)] = &[
+    (KeystrokeType::Basic, "basic"),
+    (KeystrokeType::ShortMedia, "shortMedia"),
+    (KeystrokeType::LongMedia, "longMedia"),
+    (KeystrokeType::System, "system"),
+];
+
+pub(crate) const SECONDARY_ROLES: &[(SecondaryRole, &str)] = &[
+    (SecondaryRole::LeftCtrl, "leftCtrl"),
+    (SecondaryRole::LeftShift, "leftShift"),
+    (SecondaryRole::LeftAlt, "leftAlt"),
+    (SecondaryRole::LeftSuper, "leftSuper"),
+    (SecondaryRole::RightCtrl, "rightCtrl"),
+    (SecondaryRole::RightShift, "rightShift"),
+    (SecondaryRole::RightAlt, "rightAlt"),
+    (SecondaryRole::RightSuper, "rightSuper"),
+    (SecondaryRole::Mod, "mod"),
+    (SecondaryRole::Fn, "fn"),
+    (SecondaryRole::Mouse, "mouse"),
+];
+
+pub(crate) const SWITCH_LAYER_MODES: &[(SwitchLayerMode, &str)] = &[
+    (
+        SwitchLayerMode::HoldAndDoubleTapToggle,
+        "holdAndDoubleTapToggle",
+    ),
+    (SwitchLayerMode::Toggle, "toggle"),
+    (SwitchLayerMode::Hold, "hold"),
+];
+
+pub(crate) const MOUSE_ACTIONS: &[(MouseActionParam, &str)] = &[
+    (MouseActionParam::LeftClick, "leftClick"),
+    (MouseActionParam::MiddleClick, "middleClick"),
+    (MouseActionParam::RightClick, "rightClick"),
+    (MouseActionParam::MoveUp, "moveUp"),
+    (MouseActionParam::MoveDown, "moveDown"),
+    (MouseActionParam::MoveLeft, "moveLeft"),
+    (MouseActionParam::MoveRight, "moveRight"),
+    (MouseActionParam::ScrollUp, "scrollUp"),
+    (MouseActionParam::ScrollDown, "scrollDown"),
+    (MouseActionParam::ScrollLeft, "scrollLeft"),
+    (MouseActionParam::ScrollRight, "scrollRight"),
+    (MouseActionParam::Accelerate, "accelerate"),
+    (MouseActionParam::Decelerate, "decelerate"),
+    (MouseActionParam::Button4, "button4"),
+    (MouseActionParam::Button5, "button5"),
+    (MouseActionParam::Button6, "button6"),
+    (MouseActionParam::Button7, "button7"),
+    (MouseActionParam::Button8, "button8"),
+];
+
+pub(crate) const MACRO_SUB_ACTIONS: &[(MacroSubAction, &str)] = &[
+    (MacroSubAction::Press, "press"),
+    (MacroSubAction::Hold, "hold"),
+    (MacroSubAction::Release, "release"),
+];
+
+pub(crate) const POINTER_MODES: &[(PointerMode, &str)] =
+    &[(PointerMode::Move, "move"), (PointerMode::Scroll, "scroll")];
+
+pub(crate) const POINTER_FUNCTIONS: &[(PointerFunction, &str)] = &[
+    (PointerFunction::Move, "move"),
+    (PointerFunction::Scroll, "scroll"),
+];
+
+pub(crate) const BACKLIGHTING_MODES: &[(BacklightingMode, &str)] = &[
+    (BacklightingMode::Functional, "functional"),
+    (BacklightingMode::Uniform, "uniform"),
+];
+
+pub(crate) fn name_of<T: PartialEq>(table: &[(T, &'static str)], value: T) -> &'static str {
+    table
+        .iter()
+        .find(|(candidate, _)| *candidate == value)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+pub(crate) fn find_value<T: Copy>(table: &[(T, &str)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(_, candidate)| *candidate == name)
+        .map(|(value, _)| *value)
+}
+
+pub(crate) fn layer_name(id: u8) -> String {
+    match LAYER_NUMBER_TO_STRING.get(id as usize) {
+        Some(name) => name.to_string(),
+        None => id.to_string(),
+    }
+}
+
+pub(crate) fn layer_id(name: &str) -> Option<u8> {
+    match LAYER_NUMBER_TO_STRING
+        .iter()
+        .position(|layer| *layer == name)
+    {
+        Some(id) => Some(id as u8),
+        None => name.parse().ok(),
+    }
+}
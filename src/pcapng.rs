@@ -0,0 +1,141 @@
+use crate::{
+    capture::{Direction, Record},
+    consts::UsbCommand,
+};
+use std::io::{self, Write};
+
+/// LINKTYPE_USB_LINUX_MMAPPED, the layout usbmon captures use on Linux.
+pub const LINKTYPE_USB_LINUX_MMAPPED: u16 = 220;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+const OPT_END_OF_OPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+
+const URB_INTERRUPT: u8 = 1;
+const ENDPOINT_OUT: u8 = 0x01;
+const ENDPOINT_IN: u8 = 0x81;
+const EINPROGRESS: i32 = -115;
+
+/// Writes the records as a pcapng section with one USB interface, annotating
+/// each packet with the name of the command it carries or answers.
+pub fn write_pcapng(records: &[Record], mut out: impl Write) -> io::Result<()> {
+    let mut options = vec![];
+    option(&mut options, SHB_USERAPPL, b"uhkctl");
+    block(&mut out, SECTION_HEADER_BLOCK, |body| {
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // section length unknown
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        body.extend_from_slice(&options);
+    })?;
+    block(&mut out, INTERFACE_DESCRIPTION_BLOCK, |body| {
+        body.extend_from_slice(&LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+    })?;
+    let mut urb_id = 0u64;
+    let mut command = None;
+    for record in records {
+        let comment = match record.direction {
+            Direction::Out => {
+                urb_id += 1;
+                command = record
+                    .data
+                    .first()
+                    .map(|id| match UsbCommand::try_from(*id) {
+                        Ok(command) => format!("{:?}", command),
+                        Err(_) => format!("Unknown command {:#04x}", id),
+                    });
+                command.clone().unwrap_or_default()
+            }
+            Direction::In => match &command {
+                Some(command) => format!("{} response", command),
+                None => "Unsolicited response".to_string(),
+            },
+        };
+        let packet = usbmon_packet(urb_id, record);
+        let micros = record.timestamp.as_micros() as u64;
+        let mut options = vec![];
+        option(&mut options, OPT_COMMENT, comment.as_bytes());
+        block(&mut out, ENHANCED_PACKET_BLOCK, |body| {
+            body.extend_from_slice(&0u32.to_le_bytes());
+            body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(micros as u32).to_le_bytes());
+            body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            body.extend_from_slice(&packet);
+            pad(body);
+            body.extend_from_slice(&options);
+        })?;
+    }
+    Ok(())
+}
+
+/// Builds the 64 byte usbmon header followed by the payload. Outgoing reports
+/// are URB submissions on the OUT endpoint, responses completions on IN.
+fn usbmon_packet(urb_id: u64, record: &Record) -> Vec<u8> {
+    let (event, endpoint, status) = match record.direction {
+        Direction::Out => (b'S', ENDPOINT_OUT, EINPROGRESS),
+        Direction::In => (b'C', ENDPOINT_IN, 0),
+    };
+    let length = record.data.len() as u32;
+    let mut packet = Vec::with_capacity(64 + record.data.len());
+    packet.extend_from_slice(&urb_id.to_le_bytes());
+    packet.push(event);
+    packet.push(URB_INTERRUPT);
+    packet.push(endpoint);
+    // device address
+    packet.push(1);
+    // bus id
+    packet.extend_from_slice(&1u16.to_le_bytes());
+    // no setup packet, data present
+    packet.push(b'-');
+    packet.push(0);
+    packet.extend_from_slice(&(record.timestamp.as_secs() as i64).to_le_bytes());
+    packet.extend_from_slice(&(record.timestamp.subsec_micros() as i32).to_le_bytes());
+    packet.extend_from_slice(&status.to_le_bytes());
+    packet.extend_from_slice(&length.to_le_bytes());
+    packet.extend_from_slice(&length.to_le_bytes());
+    packet.extend_from_slice(&[0; 8]);
+    // interval, start frame, transfer flags, iso descriptor count
+    packet.extend_from_slice(&1i32.to_le_bytes());
+    packet.extend_from_slice(&0i32.to_le_bytes());
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(&record.data);
+    packet
+}
+
+fn block(out: &mut impl Write, kind: u32, fill: impl FnOnce(&mut Vec<u8>)) -> io::Result<()> {
+    let mut body = vec![];
+    fill(&mut body);
+    let length = (body.len() + 12) as u32;
+    out.write_all(&kind.to_le_bytes())?;
+    out.write_all(&length.to_le_bytes())?;
+    out.write_all(&body)?;
+    out.write_all(&length.to_le_bytes())
+}
+
+fn option(options: &mut Vec<u8>, code: u16, value: &[u8]) {
+    if options.is_empty() {
+        options.extend_from_slice(&OPT_END_OF_OPT.to_le_bytes());
+        options.extend_from_slice(&0u16.to_le_bytes());
+    }
+    // keep opt_endofopt last
+    let end = options.split_off(options.len() - 4);
+    options.extend_from_slice(&code.to_le_bytes());
+    options.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    options.extend_from_slice(value);
+    pad(options);
+    options.extend_from_slice(&end);
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
@@ -0,0 +1,315 @@
+//! Keyboard shaped drawings of keymap layers for the terminal.
+
+use crate::{
+    config::{KeyAction, KeymapConfig, LayerConfig, Modifiers, UserConfig},
+    consts::{ModuleId, MouseActionParam, SwitchLayerMode, LAYER_NUMBER_TO_STRING},
+    layout::{Key, Layout},
+};
+
+/// Characters used for key borders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Unicode,
+    Ascii,
+}
+
+/// Text columns per key unit.
+const UNIT: f32 = 6.0;
+/// Text lines per key row: the border and two lines of legend.
+const ROW_HEIGHT: usize = 3;
+
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// Short legends of an action: what a tap does and, for keys with a
+/// secondary role, what holding them does. Layer switches put their mode
+/// in the second legend.
+pub fn key_label(config: &UserConfig, action: &KeyAction) -> (String, Option<String>) {
+    match action {
+        KeyAction::None => (String::new(), None),
+        KeyAction::Keystroke(keystroke) => {
+            let modifiers = keystroke
+                .modifiers
+                .filter(|modifiers| !modifiers.is_empty());
+            let key = keystroke
+                .scancode
+                .map(|scancode| match scancode.name(keystroke.atype) {
+                    Some(name) => name.strip_prefix("KEY_").unwrap_or(name).to_string(),
+                    None => scancode.to_string(),
+                });
+            let label = match (modifiers, key) {
+                (Some(modifiers), Some(key)) => format!("{}-{}", short_modifiers(modifiers), key),
+                (Some(modifiers), None) => modifiers.to_string(),
+                (None, Some(key)) => key,
+                (None, None) => String::new(),
+            };
+            (label, keystroke.secondary_role.map(|role| role.to_string()))
+        }
+        KeyAction::SwitchLayer(layer, mode) => {
+            let name = match LAYER_NUMBER_TO_STRING.get(*layer as usize) {
+                Some(name) => capitalize(name),
+                None => format!("Layer {}", layer),
+            };
+            let mode = match mode {
+                SwitchLayerMode::HoldAndDoubleTapToggle => None,
+                SwitchLayerMode::Toggle => Some("lock".to_string()),
+                SwitchLayerMode::Hold => Some("hold".to_string()),
+            };
+            (name, mode)
+        }
+        KeyAction::SwitchKeymap(index) => (
+            match config.keymaps.get(*index as usize) {
+                Some(keymap) => format!(">{}", keymap.abbr),
+                None => format!(">#{}", index),
+            },
+            None,
+        ),
+        KeyAction::MouseAction(action) => (mouse_label(*action).to_string(), None),
+        KeyAction::PlayMacroAction(index) => (
+            match config.macros.get(*index as usize) {
+                Some(macro_) => format!("M:{}", macro_.name),
+                None => format!("M:#{}", index),
+            },
+            None,
+        ),
+    }
+}
+
+/// Emacs style modifier prefix, `CS` for left control and shift.
+fn short_modifiers(modifiers: Modifiers) -> String {
+    const NAMES: [&str; 8] = ["C", "S", "A", "G", "RC", "RS", "RA", "RG"];
+    (0..8)
+        .filter(|bit| modifiers.bits() & 1 << bit != 0)
+        .map(|bit| NAMES[bit])
+        .collect()
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn mouse_label(action: MouseActionParam) -> &'static str {
+    match action {
+        MouseActionParam::LeftClick => "LClick",
+        MouseActionParam::MiddleClick => "MClick",
+        MouseActionParam::RightClick => "RClick",
+        MouseActionParam::MoveUp => "Ms Up",
+        MouseActionParam::MoveDown => "Ms Dn",
+        MouseActionParam::MoveLeft => "Ms Lt",
+        MouseActionParam::MoveRight => "Ms Rt",
+        MouseActionParam::ScrollUp => "Wh Up",
+        MouseActionParam::ScrollDown => "Wh Dn",
+        MouseActionParam::ScrollLeft => "Wh Lt",
+        MouseActionParam::ScrollRight => "Wh Rt",
+        MouseActionParam::Accelerate => "Accel",
+        MouseActionParam::Decelerate => "Decel",
+        MouseActionParam::Button4 => "Btn4",
+        MouseActionParam::Button5 => "Btn5",
+        MouseActionParam::Button6 => "Btn6",
+        MouseActionParam::Button7 => "Btn7",
+        MouseActionParam::Button8 => "Btn8",
+    }
+}
+
+/// Draws every layer of a keymap, each under a title line.
+pub fn render_keymap(
+    config: &UserConfig,
+    keymap: &KeymapConfig,
+    layout: &Layout,
+    style: Style,
+) -> String {
+    keymap
+        .layers
+        .iter()
+        .map(|layer| {
+            let name = match LAYER_NUMBER_TO_STRING.get(layer.id as usize) {
+                Some(name) => name.to_string(),
+                None => layer.id.to_string(),
+            };
+            format!(
+                "{} / {}\n{}",
+                keymap.abbr,
+                name,
+                render_layer(config, layer, layout, style)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Draws the keyboard halves side by side, followed by any add-on module
+/// the layer assigns keys to.
+pub fn render_layer(
+    config: &UserConfig,
+    layer: &LayerConfig,
+    layout: &Layout,
+    style: Style,
+) -> String {
+    let actions = |module: ModuleId| {
+        layer
+            .modules
+            .iter()
+            .find(|candidate| candidate.id == u8::from(module))
+            .map(|module| module.key_actions.as_slice())
+            .unwrap_or_default()
+    };
+    let mut canvas = Canvas::default();
+    draw(
+        &mut canvas,
+        config,
+        layout.keys(ModuleId::LeftKeyboardHalf.into()),
+        actions(ModuleId::LeftKeyboardHalf),
+        0.0,
+    );
+    draw(
+        &mut canvas,
+        config,
+        layout.keys(ModuleId::RightKeyboardHalf.into()),
+        actions(ModuleId::RightKeyboardHalf),
+        layout.width(ModuleId::LeftKeyboardHalf.into()) + 1.0,
+    );
+    let mut out = canvas.render(style);
+    for module in &layer.modules {
+        if module.id == u8::from(ModuleId::LeftKeyboardHalf)
+            || module.id == u8::from(ModuleId::RightKeyboardHalf)
+        {
+            continue;
+        }
+        let keys = layout.keys(module.id);
+        if keys.is_empty() {
+            continue;
+        }
+        let mut canvas = Canvas::default();
+        draw(&mut canvas, config, keys, &module.key_actions, 0.0);
+        out.push_str(&canvas.render(style));
+    }
+    out
+}
+
+fn draw(
+    canvas: &mut Canvas,
+    config: &UserConfig,
+    keys: &[Key],
+    actions: &[KeyAction],
+    offset: f32,
+) {
+    for key in keys {
+        let x0 = ((offset + key.column) * UNIT).round() as usize;
+        let x1 = ((offset + key.column + key.width) * UNIT).round() as usize;
+        let y0 = key.row as usize * ROW_HEIGHT;
+        let y1 = y0 + ROW_HEIGHT;
+        canvas.rect(x0, y0, x1, y1);
+        if let Some(action) = actions.get(key.id as usize) {
+            let (tap, hold) = key_label(config, action);
+            canvas.text(x0, x1, y0 + 1, &tap);
+            if let Some(hold) = hold {
+                canvas.text(x0, x1, y0 + 2, &hold);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cell {
+    Blank,
+    /// Border joining the neighbours in the given directions.
+    Border(u8),
+    Text(char),
+}
+
+#[derive(Default)]
+struct Canvas {
+    lines: Vec<Vec<Cell>>,
+}
+
+impl Canvas {
+    fn cell(&mut self, x: usize, y: usize) -> &mut Cell {
+        if self.lines.len() <= y {
+            self.lines.resize(y + 1, vec![]);
+        }
+        let line = &mut self.lines[y];
+        if line.len() <= x {
+            line.resize(x + 1, Cell::Blank);
+        }
+        &mut line[x]
+    }
+
+    fn connect(&mut self, x: usize, y: usize, direction: u8) {
+        let cell = self.cell(x, y);
+        *cell = match *cell {
+            Cell::Border(directions) => Cell::Border(directions | direction),
+            _ => Cell::Border(direction),
+        };
+    }
+
+    fn rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        for x in x0..x1 {
+            for y in [y0, y1] {
+                self.connect(x, y, RIGHT);
+                self.connect(x + 1, y, LEFT);
+            }
+        }
+        for y in y0..y1 {
+            for x in [x0, x1] {
+                self.connect(x, y, DOWN);
+                self.connect(x, y + 1, UP);
+            }
+        }
+    }
+
+    /// Centers text between two borders, cutting it to fit.
+    fn text(&mut self, x0: usize, x1: usize, y: usize, text: &str) {
+        let room = x1.saturating_sub(x0 + 1);
+        let chars: Vec<_> = text.chars().take(room).collect();
+        let start = x0 + 1 + (room - chars.len()) / 2;
+        for (i, c) in chars.into_iter().enumerate() {
+            *self.cell(start + i, y) = Cell::Text(c);
+        }
+    }
+
+    fn render(&self, style: Style) -> String {
+        let mut out = String::new();
+        for line in &self.lines {
+            let line: String = line
+                .iter()
+                .map(|cell| match *cell {
+                    Cell::Blank => ' ',
+                    Cell::Border(directions) => border(directions, style),
+                    Cell::Text(c) => c,
+                })
+                .collect();
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn border(directions: u8, style: Style) -> char {
+    let horizontal = directions & (UP | DOWN) == 0;
+    let vertical = directions & (LEFT | RIGHT) == 0;
+    match style {
+        Style::Ascii if horizontal => '-',
+        Style::Ascii if vertical => '|',
+        Style::Ascii => '+',
+        Style::Unicode if horizontal => '─',
+        Style::Unicode if vertical => '│',
+        Style::Unicode => match directions {
+            d if d == DOWN | RIGHT => '┌',
+            d if d == DOWN | LEFT => '┐',
+            d if d == UP | RIGHT => '└',
+            d if d == UP | LEFT => '┘',
+            d if d == UP | DOWN | RIGHT => '├',
+            d if d == UP | DOWN | LEFT => '┤',
+            d if d == DOWN | LEFT | RIGHT => '┬',
+            d if d == UP | LEFT | RIGHT => '┴',
+            _ => '┼',
+        },
+    }
+}
@@ -0,0 +1,232 @@
+//! Names for the HID usages a keystroke scancode refers to, chosen by its
+//! `KeystrokeType`: the keyboard page for basic keystrokes, the consumer page
+//! for short and long media keys and generic desktop system controls.
+
+use crate::consts::KeystrokeType;
+
+const KEYBOARD: &[(u16, &str)] = &[
+    (0x04, "KEY_A"),
+    (0x05, "KEY_B"),
+    (0x06, "KEY_C"),
+    (0x07, "KEY_D"),
+    (0x08, "KEY_E"),
+    (0x09, "KEY_F"),
+    (0x0a, "KEY_G"),
+    (0x0b, "KEY_H"),
+    (0x0c, "KEY_I"),
+    (0x0d, "KEY_J"),
+    (0x0e, "KEY_K"),
+    (0x0f, "KEY_L"),
+    (0x10, "KEY_M"),
+    (0x11, "KEY_N"),
+    (0x12, "KEY_O"),
+    (0x13, "KEY_P"),
+    (0x14, "KEY_Q"),
+    (0x15, "KEY_R"),
+    (0x16, "KEY_S"),
+    (0x17, "KEY_T"),
+    (0x18, "KEY_U"),
+    (0x19, "KEY_V"),
+    (0x1a, "KEY_W"),
+    (0x1b, "KEY_X"),
+    (0x1c, "KEY_Y"),
+    (0x1d, "KEY_Z"),
+    (0x1e, "KEY_1"),
+    (0x1f, "KEY_2"),
+    (0x20, "KEY_3"),
+    (0x21, "KEY_4"),
+    (0x22, "KEY_5"),
+    (0x23, "KEY_6"),
+    (0x24, "KEY_7"),
+    (0x25, "KEY_8"),
+    (0x26, "KEY_9"),
+    (0x27, "KEY_0"),
+    (0x28, "KEY_ENTER"),
+    (0x29, "KEY_ESCAPE"),
+    (0x2a, "KEY_BACKSPACE"),
+    (0x2b, "KEY_TAB"),
+    (0x2c, "KEY_SPACE"),
+    (0x2d, "KEY_MINUS"),
+    (0x2e, "KEY_EQUAL"),
+    (0x2f, "KEY_LEFT_BRACKET"),
+    (0x30, "KEY_RIGHT_BRACKET"),
+    (0x31, "KEY_BACKSLASH"),
+    (0x32, "KEY_NON_US_HASH"),
+    (0x33, "KEY_SEMICOLON"),
+    (0x34, "KEY_APOSTROPHE"),
+    (0x35, "KEY_GRAVE"),
+    (0x36, "KEY_COMMA"),
+    (0x37, "KEY_DOT"),
+    (0x38, "KEY_SLASH"),
+    (0x39, "KEY_CAPS_LOCK"),
+    (0x3a, "KEY_F1"),
+    (0x3b, "KEY_F2"),
+    (0x3c, "KEY_F3"),
+    (0x3d, "KEY_F4"),
+    (0x3e, "KEY_F5"),
+    (0x3f, "KEY_F6"),
+    (0x40, "KEY_F7"),
+    (0x41, "KEY_F8"),
+    (0x42, "KEY_F9"),
+    (0x43, "KEY_F10"),
+    (0x44, "KEY_F11"),
+    (0x45, "KEY_F12"),
+    (0x46, "KEY_PRINT_SCREEN"),
+    (0x47, "KEY_SCROLL_LOCK"),
+    (0x48, "KEY_PAUSE"),
+    (0x49, "KEY_INSERT"),
+    (0x4a, "KEY_HOME"),
+    (0x4b, "KEY_PAGE_UP"),
+    (0x4c, "KEY_DELETE"),
+    (0x4d, "KEY_END"),
+    (0x4e, "KEY_PAGE_DOWN"),
+    (0x4f, "KEY_RIGHT"),
+    (0x50, "KEY_LEFT"),
+    (0x51, "KEY_DOWN"),
+    (0x52, "KEY_UP"),
+    (0x53, "KEY_NUM_LOCK"),
+    (0x54, "KEY_KP_SLASH"),
+    (0x55, "KEY_KP_ASTERISK"),
+    (0x56, "KEY_KP_MINUS"),
+    (0x57, "KEY_KP_PLUS"),
+    (0x58, "KEY_KP_ENTER"),
+    (0x59, "KEY_KP_1"),
+    (0x5a, "KEY_KP_2"),
+    (0x5b, "KEY_KP_3"),
+    (0x5c, "KEY_KP_4"),
+    (0x5d, "KEY_KP_5"),
+    (0x5e, "KEY_KP_6"),
+    (0x5f, "KEY_KP_7"),
+    (0x60, "KEY_KP_8"),
+    (0x61, "KEY_KP_9"),
+    (0x62, "KEY_KP_0"),
+    (0x63, "KEY_KP_DOT"),
+    (0x64, "KEY_NON_US_BACKSLASH"),
+    (0x65, "KEY_APPLICATION"),
+    (0x66, "KEY_POWER"),
+    (0x67, "KEY_KP_EQUAL"),
+    (0x68, "KEY_F13"),
+    (0x69, "KEY_F14"),
+    (0x6a, "KEY_F15"),
+    (0x6b, "KEY_F16"),
+    (0x6c, "KEY_F17"),
+    (0x6d, "KEY_F18"),
+    (0x6e, "KEY_F19"),
+    (0x6f, "KEY_F20"),
+    (0x70, "KEY_F21"),
+    (0x71, "KEY_F22"),
+    (0x72, "KEY_F23"),
+    (0x73, "KEY_F24"),
+    (0x74, "KEY_EXECUTE"),
+    (0x75, "KEY_HELP"),
+    (0x76, "KEY_MENU"),
+    (0x77, "KEY_SELECT"),
+    (0x78, "KEY_STOP"),
+    (0x79, "KEY_AGAIN"),
+    (0x7a, "KEY_UNDO"),
+    (0x7b, "KEY_CUT"),
+    (0x7c, "KEY_COPY"),
+    (0x7d, "KEY_PASTE"),
+    (0x7e, "KEY_FIND"),
+    (0x7f, "KEY_MUTE"),
+    (0x80, "KEY_VOLUME_UP"),
+    (0x81, "KEY_VOLUME_DOWN"),
+    (0x85, "KEY_KP_COMMA"),
+    (0x87, "KEY_INTERNATIONAL1"),
+    (0x88, "KEY_INTERNATIONAL2"),
+    (0x89, "KEY_INTERNATIONAL3"),
+    (0x8a, "KEY_INTERNATIONAL4"),
+    (0x8b, "KEY_INTERNATIONAL5"),
+    (0x8c, "KEY_INTERNATIONAL6"),
+    (0x8d, "KEY_INTERNATIONAL7"),
+    (0x8e, "KEY_INTERNATIONAL8"),
+    (0x8f, "KEY_INTERNATIONAL9"),
+    (0x90, "KEY_LANG1"),
+    (0x91, "KEY_LANG2"),
+    (0x92, "KEY_LANG3"),
+    (0x93, "KEY_LANG4"),
+    (0x94, "KEY_LANG5"),
+    (0x95, "KEY_LANG6"),
+    (0x96, "KEY_LANG7"),
+    (0x97, "KEY_LANG8"),
+    (0x98, "KEY_LANG9"),
+    (0xe0, "KEY_LEFT_CTRL"),
+    (0xe1, "KEY_LEFT_SHIFT"),
+    (0xe2, "KEY_LEFT_ALT"),
+    (0xe3, "KEY_LEFT_GUI"),
+    (0xe4, "KEY_RIGHT_CTRL"),
+    (0xe5, "KEY_RIGHT_SHIFT"),
+    (0xe6, "KEY_RIGHT_ALT"),
+    (0xe7, "KEY_RIGHT_GUI"),
+];
+
+const CONSUMER: &[(u16, &str)] = &[
+    (0x06f, "BrightnessUp"),
+    (0x070, "BrightnessDown"),
+    (0x0b0, "Play"),
+    (0x0b1, "Pause"),
+    (0x0b2, "Record"),
+    (0x0b3, "FastForward"),
+    (0x0b4, "Rewind"),
+    (0x0b5, "NextTrack"),
+    (0x0b6, "PreviousTrack"),
+    (0x0b7, "Stop"),
+    (0x0b8, "Eject"),
+    (0x0cd, "PlayPause"),
+    (0x0e2, "Mute"),
+    (0x0e9, "VolumeUp"),
+    (0x0ea, "VolumeDown"),
+    (0x183, "MediaSelect"),
+    (0x18a, "Mail"),
+    (0x192, "Calculator"),
+    (0x194, "MyComputer"),
+    (0x221, "WwwSearch"),
+    (0x223, "WwwHome"),
+    (0x224, "WwwBack"),
+    (0x225, "WwwForward"),
+    (0x226, "WwwStop"),
+    (0x227, "WwwRefresh"),
+    (0x22a, "WwwFavorites"),
+];
+
+const SYSTEM: &[(u16, &str)] = &[
+    (0x81, "SystemPowerDown"),
+    (0x82, "SystemSleep"),
+    (0x83, "SystemWakeUp"),
+];
+
+/// Every named usage of the page keystrokes of `atype` refer to.
+pub fn table(atype: KeystrokeType) -> &'static [(u16, &'static str)] {
+    match atype {
+        KeystrokeType::Basic => KEYBOARD,
+        KeystrokeType::ShortMedia | KeystrokeType::LongMedia => CONSUMER,
+        KeystrokeType::System => SYSTEM,
+    }
+}
+
+pub fn name(atype: KeystrokeType, scancode: u16) -> Option<&'static str> {
+    table(atype)
+        .iter()
+        .find(|(code, _)| *code == scancode)
+        .map(|(_, name)| *name)
+}
+
+/// Resolves a canonical name, picking short media for consumer usages that
+/// fit in a byte and long media otherwise.
+pub fn lookup(name: &str) -> Option<(KeystrokeType, u16)> {
+    [
+        KeystrokeType::Basic,
+        KeystrokeType::ShortMedia,
+        KeystrokeType::System,
+    ]
+    .into_iter()
+    .find_map(|atype| {
+        table(atype)
+            .iter()
+            .find(|(_, candidate)| *candidate == name)
+            .map(|(code, _)| match atype {
+                KeystrokeType::ShortMedia if *code > 0xff => (KeystrokeType::LongMedia, *code),
+                _ => (atype, *code),
+            })
+    })
+}
@@ -0,0 +1,190 @@
+//! Printable SVG cheat sheets of keymaps: one panel per layer, with what a
+//! key does when held written in its lower right corner.
+
+use crate::{
+    config::{KeyAction, KeymapConfig, LayerConfig, UserConfig},
+    consts::{ModuleId, LAYER_NUMBER_TO_STRING},
+    layout::Layout,
+    render::key_label,
+};
+use std::fmt::Write;
+
+/// Pixels per key unit.
+const UNIT: f32 = 54.0;
+/// Space left between neighbouring keys.
+const GAP: f32 = 4.0;
+const MARGIN: f32 = 20.0;
+const TITLE_HEIGHT: f32 = 32.0;
+const TAP_FONT_SIZE: f32 = 13.0;
+
+const STYLE: &str = "\
+.title { font: bold 16px sans-serif; }
+.key { fill: #fff; stroke: #333; stroke-width: 1.5; }
+.key.empty { fill: #eee; stroke: #999; }
+.tap { font: 13px sans-serif; text-anchor: middle; dominant-baseline: central; }
+.hold { font: 9px sans-serif; fill: #666; text-anchor: end; }
+";
+
+/// Renders all layers of a keymap as a standalone SVG document.
+pub fn render_keymap(config: &UserConfig, keymap: &KeymapConfig, layout: &Layout) -> String {
+    let left_width = layout.width(ModuleId::LeftKeyboardHalf.into());
+    let right_offset = left_width + 1.0;
+    let width = (right_offset + layout.width(ModuleId::RightKeyboardHalf.into())) * UNIT;
+    let mut body = String::new();
+    let mut y = MARGIN;
+    for layer in &keymap.layers {
+        y += render_layer(&mut body, config, keymap, layer, layout, right_offset, y) + MARGIN;
+    }
+    let width = width + 2.0 * MARGIN;
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <style>\n{style}</style>\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>\n\
+         {body}</svg>\n",
+        w = width,
+        h = y,
+        style = STYLE,
+        body = body,
+    )
+}
+
+/// Draws one layer panel at the given height, returning the panel height.
+fn render_layer(
+    out: &mut String,
+    config: &UserConfig,
+    keymap: &KeymapConfig,
+    layer: &LayerConfig,
+    layout: &Layout,
+    right_offset: f32,
+    top: f32,
+) -> f32 {
+    let name = match LAYER_NUMBER_TO_STRING.get(layer.id as usize) {
+        Some(name) => name.to_string(),
+        None => format!("layer {}", layer.id),
+    };
+    writeln!(
+        out,
+        "<text class=\"title\" x=\"{}\" y=\"{}\">{} ({}) / {}</text>",
+        MARGIN,
+        top + 20.0,
+        escape(&keymap.name),
+        escape(&keymap.abbr),
+        escape(&name)
+    )
+    .unwrap();
+    let top = top + TITLE_HEIGHT;
+    let actions = |module: u8| {
+        layer
+            .modules
+            .iter()
+            .find(|candidate| candidate.id == module)
+            .map(|module| module.key_actions.as_slice())
+            .unwrap_or_default()
+    };
+    let mut height: f32 = 0.0;
+    for (module, offset) in [
+        (ModuleId::LeftKeyboardHalf, 0.0),
+        (ModuleId::RightKeyboardHalf, right_offset),
+    ] {
+        let module = u8::from(module);
+        render_module(
+            out,
+            config,
+            layout,
+            module,
+            actions(module),
+            MARGIN + offset * UNIT,
+            top,
+        );
+        height = height.max(layout.row_count(module) as f32 * UNIT);
+    }
+    // add-on modules go in a row of their own below the halves
+    let mut x = MARGIN;
+    let mut addons: f32 = 0.0;
+    for module in &layer.modules {
+        if module.id == u8::from(ModuleId::LeftKeyboardHalf)
+            || module.id == u8::from(ModuleId::RightKeyboardHalf)
+            || layout.keys(module.id).is_empty()
+        {
+            continue;
+        }
+        let y = top + height + UNIT / 2.0;
+        render_module(out, config, layout, module.id, &module.key_actions, x, y);
+        x += (layout.width(module.id) + 1.0) * UNIT;
+        addons = addons.max(UNIT / 2.0 + layout.row_count(module.id) as f32 * UNIT);
+    }
+    TITLE_HEIGHT + height + addons
+}
+
+fn render_module(
+    out: &mut String,
+    config: &UserConfig,
+    layout: &Layout,
+    module: u8,
+    actions: &[KeyAction],
+    left: f32,
+    top: f32,
+) {
+    for key in layout.keys(module) {
+        let x = left + key.column * UNIT + GAP / 2.0;
+        let y = top + key.row as f32 * UNIT + GAP / 2.0;
+        let w = key.width * UNIT - GAP;
+        let h = UNIT - GAP;
+        let (tap, hold) = match actions.get(key.id as usize) {
+            Some(action) => key_label(config, action),
+            None => (String::new(), None),
+        };
+        let class = if tap.is_empty() && hold.is_none() {
+            "key empty"
+        } else {
+            "key"
+        };
+        writeln!(
+            out,
+            "<rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"4\"/>",
+            class, x, y, w, h
+        )
+        .unwrap();
+        if !tap.is_empty() {
+            // squeeze legends that would overflow the key
+            let fit = w - 8.0;
+            let squeeze = if tap.chars().count() as f32 * TAP_FONT_SIZE * 0.6 > fit {
+                format!(" textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\"", fit)
+            } else {
+                String::new()
+            };
+            writeln!(
+                out,
+                "<text class=\"tap\" x=\"{}\" y=\"{}\"{}>{}</text>",
+                x + w / 2.0,
+                y + h / 2.0,
+                squeeze,
+                escape(&tap)
+            )
+            .unwrap();
+        }
+        if let Some(hold) = hold {
+            writeln!(
+                out,
+                "<text class=\"hold\" x=\"{}\" y=\"{}\">{}</text>",
+                x + w - 4.0,
+                y + h - 5.0,
+                escape(&hold)
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
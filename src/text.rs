@@ -0,0 +1,875 @@
+//! A text form of the user config meant to be kept under version control.
+//! Settings and macros are plain TOML, and every layer holds one grid per
+//! module whose rows follow the rows of the physical keyboard, key ids
+//! running in reading order as in `layout`.
+//!
+//! Grid tokens are separated by whitespace:
+//!
+//! - `_` leaves the key unassigned
+//! - `KEY_A`, `LCtrl+KEY_C`, `LShift`, `KEY_SPACE/Fn` are keystrokes with
+//!   optional modifiers and a secondary role after the slash; scancodes
+//!   without a name are written `basic:0x68`, `shortMedia:0xe2`, ...
+//! - `-` is a keystroke without key or modifiers, `shortMedia:-` one of
+//!   another type without a key, and `NoMods` an empty set of modifiers
+//!   that is stored nonetheless
+//! - `layer:fn`, `layer:mod:toggle`, `layer:mouse:hold` switch layers
+//! - `keymap:DVO` switches to the keymap with that abbreviation
+//! - `mouse:leftClick` triggers a mouse action
+//! - `macro:Name` or `macro:"Two words"` plays a macro by name, `macro:#3`
+//!   by index
+//!
+//! Macro actions are strings such as `press LCtrl+KEY_C`, `hold buttons 1`,
+//! `move 10 -5`, `scroll 0 1`, `delay 100`, `text hello` or `command ...`.
+//!
+//! Shared definitions can live in other files listed in `include`, paths
+//! being relative to the including file. Included tables are merged with
+//! the including file taking precedence, and arrays such as `macros` and
+//! `keymaps` are concatenated. A file included more than once, such as a
+//! base shared by two includes, is only read the first time. The `[aliases]` table names tokens that grids
+//! then use as `$name`, and `[layers.NAME]` declares layers that keymap
+//! layers, or other library layers, pull in with `extends = "NAME"`. A layer
+//! extending another takes over its grids, keeping the parent's key wherever
+//! its own grid has `.` or ends early.
+
+use crate::{
+    config::{
+        Backlighting, DataModel, KeyAction, KeymapConfig, Keystroke, LayerConfig, Macro,
+        MacroAction, Modifiers, ModuleConfig, ModuleConfiguration, MouseConfig, Scancode,
+        UserConfig,
+    },
+    consts::{KeystrokeType, ModuleId, SecondaryRole, SwitchLayerMode},
+    device::{ConfigPath, PathSegment, Version},
+    layout,
+    names::{
+        find_value, layer_id, layer_name, name_of, KEYSTROKE_TYPES, MACRO_SUB_ACTIONS,
+        MOUSE_ACTIONS, SWITCH_LAYER_MODES,
+    },
+    scancodes,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TextError {
+    #[error("toml parse error")]
+    Parse(#[from] toml::de::Error),
+    #[error("toml serialization error")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("io error")]
+    IO(#[from] std::io::Error),
+    #[error("in {}", path.display())]
+    File {
+        path: PathBuf,
+        source: Box<TextError>,
+    },
+    #[error("{} includes itself", .0.display())]
+    IncludeCycle(PathBuf),
+    #[error("layer {0:?} extends itself")]
+    ExtendsCycle(String),
+    #[error("{path}: invalid {what} {token:?}")]
+    Invalid {
+        what: &'static str,
+        token: String,
+        path: ConfigPath,
+    },
+    #[error("{path}: grid row {row} has {keys} keys, the module has {expected}")]
+    GridRow {
+        row: usize,
+        keys: usize,
+        expected: usize,
+        path: ConfigPath,
+    },
+}
+
+impl TextError {
+    fn invalid(what: &'static str, token: impl ToString) -> Self {
+        Self::Invalid {
+            what,
+            token: token.to_string(),
+            path: ConfigPath::default(),
+        }
+    }
+
+    /// Prefixes the config path of an invalid token or grid with an
+    /// enclosing element.
+    fn context(mut self, segment: PathSegment) -> Self {
+        if let Self::Invalid { ref mut path, .. } | Self::GridRow { ref mut path, .. } = self {
+            path.0.insert(0, segment);
+        }
+        self
+    }
+}
+
+pub type TextResult<T> = Result<T, TextError>;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TextConfig {
+    name: String,
+    version: Version,
+    double_tap_switch_layer_timeout: u16,
+    icons_and_layer_texts_brightness: u8,
+    alphanumeric_segments_brightness: u8,
+    key_backlight_brightness: u8,
+    /// Only written for data models that store it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backlighting: Option<Backlighting>,
+    mouse: MouseConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    modules: Vec<ModuleConfiguration>,
+    #[serde(default)]
+    macros: Vec<TextMacro>,
+    #[serde(default)]
+    keymaps: Vec<TextKeymap>,
+    /// Layers that others extend by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    layers: BTreeMap<String, TextLayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TextMacro {
+    name: String,
+    #[serde(default)]
+    looped: bool,
+    #[serde(default)]
+    private: bool,
+    actions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TextKeymap {
+    abbr: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    default: bool,
+    layers: Vec<TextLayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TextLayer {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extends: Option<String>,
+    /// Key grids by module name.
+    #[serde(flatten)]
+    modules: BTreeMap<String, String>,
+}
+
+/// Compiles the text form into a user config ready to be serialized,
+/// resolving includes relative to the current directory.
+pub fn compile(text: &str) -> TextResult<UserConfig> {
+    compile_table(resolve_includes(
+        toml::from_str(text)?,
+        Path::new(""),
+        &mut Includes::default(),
+    )?)
+}
+
+/// Compiles a text form file and the files it includes.
+pub fn compile_file(path: &Path) -> TextResult<UserConfig> {
+    compile_table(load(path, &mut Includes::default())?)
+}
+
+/// Files of one compilation: those being included, to catch cycles, and all
+/// loaded so far, so that a file included twice contributes its arrays once.
+#[derive(Default)]
+struct Includes {
+    stack: Vec<PathBuf>,
+    loaded: BTreeSet<PathBuf>,
+}
+
+fn load(path: &Path, includes: &mut Includes) -> TextResult<toml::Table> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if includes.stack.contains(&canonical) {
+        return Err(TextError::IncludeCycle(path.to_path_buf()));
+    }
+    if !includes.loaded.insert(canonical.clone()) {
+        return Ok(toml::Table::new());
+    }
+    includes.stack.push(canonical);
+    let table = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let table = resolve_includes(table, path.parent().unwrap_or(Path::new("")), includes)?;
+    includes.stack.pop();
+    Ok(table)
+}
+
+/// Replaces the `include` list of a table with the merged contents of the
+/// files it names.
+fn resolve_includes(
+    mut table: toml::Table,
+    dir: &Path,
+    includes: &mut Includes,
+) -> TextResult<toml::Table> {
+    let paths: Vec<String> = match table.remove("include") {
+        Some(paths) => paths.try_into()?,
+        None => return Ok(table),
+    };
+    let mut merged = toml::Table::new();
+    for include in paths {
+        let path = dir.join(include);
+        let included = load(&path, includes).map_err(|err| match err {
+            TextError::IncludeCycle(_) => err,
+            _ => TextError::File {
+                path,
+                source: Box::new(err),
+            },
+        })?;
+        merge(&mut merged, included);
+    }
+    merge(&mut merged, table);
+    Ok(merged)
+}
+
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (Some(toml::Value::Array(base)), toml::Value::Array(overlay)) => base.extend(overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn compile_table(table: toml::Table) -> TextResult<UserConfig> {
+    let text: TextConfig = table.try_into()?;
+    if DataModel::of(text.version).is_none() {
+        return Err(TextError::invalid("version", text.version));
+    }
+    let names = Names {
+        aliases: &text.aliases,
+        keymaps: text
+            .keymaps
+            .iter()
+            .map(|keymap| keymap.abbr.as_str())
+            .collect(),
+        macros: text
+            .macros
+            .iter()
+            .map(|macro_| macro_.name.as_str())
+            .collect(),
+    };
+    Ok(UserConfig {
+        major: text.version.major,
+        minor: text.version.minor,
+        patch: text.version.patch,
+        // filled in by UserConfig::serialize
+        length: 0,
+        name: text.name,
+        double_tap_switch_layer_timeout: text.double_tap_switch_layer_timeout,
+        icons_and_layer_texts_brightness: text.icons_and_layer_texts_brightness,
+        alphanumeric_segments_brighrness: text.alphanumeric_segments_brightness,
+        key_backlight_brightness: text.key_backlight_brightness,
+        backlighting: text.backlighting.unwrap_or_default(),
+        mouse_config: text.mouse,
+        module_configurations: text.modules,
+        macros: text
+            .macros
+            .iter()
+            .enumerate()
+            .map(|(i, macro_)| {
+                compile_macro(macro_).map_err(|err| err.context(PathSegment::Macro(i)))
+            })
+            .try_collect()?,
+        keymaps: text
+            .keymaps
+            .iter()
+            .enumerate()
+            .map(|(i, keymap)| {
+                compile_keymap(keymap, &names, &text.layers)
+                    .map_err(|err| err.context(PathSegment::Keymap(i)))
+            })
+            .try_collect()?,
+    })
+}
+
+/// Renders a user config in the text form.
+pub fn decompile(config: &UserConfig) -> TextResult<String> {
+    let names = Names {
+        aliases: &BTreeMap::new(),
+        keymaps: config
+            .keymaps
+            .iter()
+            .map(|keymap| keymap.abbr.as_str())
+            .collect(),
+        macros: config
+            .macros
+            .iter()
+            .map(|macro_| macro_.name.as_str())
+            .collect(),
+    };
+    let text = TextConfig {
+        name: config.name.clone(),
+        version: Version::new(config.major, config.minor, config.patch),
+        double_tap_switch_layer_timeout: config.double_tap_switch_layer_timeout,
+        icons_and_layer_texts_brightness: config.icons_and_layer_texts_brightness,
+        alphanumeric_segments_brightness: config.alphanumeric_segments_brighrness,
+        key_backlight_brightness: config.key_backlight_brightness,
+        backlighting: (DataModel::of(config.version()) >= Some(DataModel::V6))
+            .then(|| config.backlighting.clone()),
+        mouse: config.mouse_config.clone(),
+        aliases: BTreeMap::new(),
+        modules: config.module_configurations.clone(),
+        macros: config.macros.iter().map(decompile_macro).collect(),
+        keymaps: config
+            .keymaps
+            .iter()
+            .enumerate()
+            .map(|(i, keymap)| {
+                decompile_keymap(keymap, &names).map_err(|err| err.context(PathSegment::Keymap(i)))
+            })
+            .try_collect()?,
+        layers: BTreeMap::new(),
+    };
+    Ok(toml::to_string_pretty(&text)?)
+}
+
+/// Aliases, keymap abbreviations and macro names that grid tokens refer to.
+struct Names<'a> {
+    aliases: &'a BTreeMap<String, String>,
+    keymaps: Vec<&'a str>,
+    macros: Vec<&'a str>,
+}
+
+const MODULE_NAMES: &[(ModuleId, &str)] = &[
+    (ModuleId::RightKeyboardHalf, "right"),
+    (ModuleId::LeftKeyboardHalf, "left"),
+    (ModuleId::KeyClusterLeft, "key-cluster"),
+    (ModuleId::TrackballRight, "trackball"),
+    (ModuleId::TrackpointRight, "trackpoint"),
+    (ModuleId::TouchpadRight, "touchpad"),
+];
+
+fn module_name(id: u8) -> String {
+    match ModuleId::try_from(id) {
+        Ok(module) => name_of(MODULE_NAMES, module).to_string(),
+        Err(_) => id.to_string(),
+    }
+}
+
+fn module_id(name: &str) -> Option<u8> {
+    find_value(MODULE_NAMES, name)
+        .map(u8::from)
+        .or_else(|| name.parse().ok())
+}
+
+fn compile_keymap(
+    keymap: &TextKeymap,
+    names: &Names,
+    library: &BTreeMap<String, TextLayer>,
+) -> TextResult<KeymapConfig> {
+    Ok(KeymapConfig {
+        abbr: keymap.abbr.clone(),
+        default: keymap.default,
+        name: keymap.name.clone(),
+        desc: keymap.description.clone(),
+        layers: keymap
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                compile_layer(layer, names, library)
+                    .map_err(|err| err.context(PathSegment::Layer(i)))
+            })
+            .try_collect()?,
+    })
+}
+
+/// A layer with everything it extends folded in: its layer name and the
+/// tokens of each module grid.
+type ResolvedLayer = (Option<String>, BTreeMap<String, Vec<String>>);
+
+fn resolve_layer(
+    layer: &TextLayer,
+    library: &BTreeMap<String, TextLayer>,
+    chain: &mut Vec<String>,
+) -> TextResult<ResolvedLayer> {
+    let (mut name, mut modules) = match &layer.extends {
+        Some(parent) => {
+            if chain.contains(parent) {
+                return Err(TextError::ExtendsCycle(parent.clone()));
+            }
+            let base = library
+                .get(parent)
+                .ok_or_else(|| TextError::invalid("extended layer", parent))?;
+            chain.push(parent.clone());
+            let resolved = resolve_layer(base, library, chain)?;
+            chain.pop();
+            resolved
+        }
+        None => (None, BTreeMap::new()),
+    };
+    if layer.layer.is_some() {
+        name = layer.layer.clone();
+    }
+    for (module, grid) in &layer.modules {
+        let id = module_id(module);
+        let in_module = |err: TextError| match id {
+            Some(id) => err.context(PathSegment::Module(id as usize)),
+            None => err,
+        };
+        if let Some(id) = id {
+            check_grid(id, grid).map_err(in_module)?;
+        }
+        let inherited = modules.remove(module).unwrap_or_default();
+        let own = tokenize(grid);
+        let tokens = (0..own.len().max(inherited.len()))
+            .map(|key| match (own.get(key), inherited.get(key)) {
+                (Some(token), _) if token != "." => Ok(token.clone()),
+                (_, Some(token)) => Ok(token.clone()),
+                _ => Err(in_module(
+                    TextError::invalid("inherited key", ".").context(PathSegment::Key(key)),
+                )),
+            })
+            .collect::<TextResult<_>>()?;
+        modules.insert(module.clone(), tokens);
+    }
+    Ok((name, modules))
+}
+
+/// Checks that the lines of a grid follow the rows of its module. The last
+/// line may end early, and lines past the rows hold keys without a layout.
+fn check_grid(module: u8, grid: &str) -> TextResult<()> {
+    let rows = layout::rows(module);
+    let lines: Vec<_> = grid
+        .lines()
+        .map(tokenize)
+        .filter(|tokens| !tokens.is_empty())
+        .collect();
+    for (row, (tokens, &expected)) in lines.iter().zip(&rows).enumerate() {
+        let last = row + 1 == lines.len();
+        if tokens.len() > expected || tokens.len() < expected && !last {
+            return Err(TextError::GridRow {
+                row,
+                keys: tokens.len(),
+                expected,
+                path: ConfigPath::default(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn compile_layer(
+    layer: &TextLayer,
+    names: &Names,
+    library: &BTreeMap<String, TextLayer>,
+) -> TextResult<LayerConfig> {
+    let (name, grids) = resolve_layer(layer, library, &mut vec![])?;
+    let name = name.ok_or_else(|| TextError::invalid("layer", ""))?;
+    let id = layer_id(&name).ok_or_else(|| TextError::invalid("layer", &name))?;
+    let mut modules: Vec<ModuleConfig> = grids
+        .iter()
+        .map(|(name, tokens)| {
+            let id = module_id(name).ok_or_else(|| TextError::invalid("module", name))?;
+            Ok(ModuleConfig {
+                id,
+                key_actions: tokens
+                    .iter()
+                    .enumerate()
+                    .map(|(key, token)| {
+                        expand_alias(token, names)
+                            .and_then(|token| parse_key_action(token, names))
+                            .map_err(|err| err.context(PathSegment::Key(key)))
+                    })
+                    .collect::<TextResult<_>>()
+                    .map_err(|err| err.context(PathSegment::Module(id as usize)))?,
+            })
+        })
+        .collect::<TextResult<_>>()?;
+    modules.sort_by_key(|module| module.id);
+    Ok(LayerConfig { id, modules })
+}
+
+fn expand_alias<'a>(token: &'a str, names: &'a Names) -> TextResult<&'a str> {
+    match token.strip_prefix('$') {
+        Some(alias) => names
+            .aliases
+            .get(alias)
+            .map(String::as_str)
+            .ok_or_else(|| TextError::invalid("alias", token)),
+        None => Ok(token),
+    }
+}
+
+fn decompile_keymap(keymap: &KeymapConfig, names: &Names) -> TextResult<TextKeymap> {
+    Ok(TextKeymap {
+        abbr: keymap.abbr.clone(),
+        name: keymap.name.clone(),
+        description: keymap.desc.clone(),
+        default: keymap.default,
+        layers: keymap
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                Ok(TextLayer {
+                    layer: Some(layer_name(layer.id)),
+                    extends: None,
+                    modules: layer
+                        .modules
+                        .iter()
+                        .map(|module| {
+                            let tokens: Vec<_> = module
+                                .key_actions
+                                .iter()
+                                .map(|action| format_key_action(action, names))
+                                .collect::<TextResult<_>>()?;
+                            Ok((
+                                module_name(module.id),
+                                grid(&tokens, &layout::rows(module.id)),
+                            ))
+                        })
+                        .collect::<TextResult<_>>()
+                        .map_err(|err| err.context(PathSegment::Layer(i)))?,
+                })
+            })
+            .collect::<TextResult<_>>()?,
+    })
+}
+
+/// Lays tokens out in rows, padding every column to its widest token. Keys
+/// past the last row, and all keys of modules without a layout, share one
+/// row.
+fn grid(tokens: &[String], rows: &[usize]) -> String {
+    let mut lines = vec![];
+    let mut rest = tokens;
+    for &len in rows {
+        if rest.is_empty() {
+            break;
+        }
+        let (line, tail) = rest.split_at(len.min(rest.len()));
+        lines.push(line);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        lines.push(rest);
+    }
+    let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let widths: Vec<_> = (0..columns)
+        .map(|column| {
+            lines
+                .iter()
+                .filter_map(|line| line.get(column))
+                .map(|token| token.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    for line in lines {
+        let cells: Vec<_> = line
+            .iter()
+            .zip(&widths)
+            .map(|(token, width)| format!("{:width$}", token, width = width))
+            .collect();
+        out.push_str(cells.join(" ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Splits a grid on whitespace, keeping double quoted runs together.
+fn tokenize(grid: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in grid.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if c.is_whitespace() && !quoted {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Refers to an entry by name when that is unambiguous, by index otherwise.
+fn reference(names: &[&str], index: u8) -> String {
+    match names.get(index as usize) {
+        Some(name)
+            if !name.is_empty()
+                && !name.contains('"')
+                && names.iter().filter(|other| *other == name).count() == 1 =>
+        {
+            if name.contains(char::is_whitespace) {
+                format!("\"{}\"", name)
+            } else {
+                name.to_string()
+            }
+        }
+        _ => format!("#{}", index),
+    }
+}
+
+fn resolve(names: &[&str], reference: &str) -> Option<u8> {
+    if let Some(index) = reference.strip_prefix('#') {
+        return index.parse().ok();
+    }
+    let name = reference
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+        .unwrap_or(reference);
+    names
+        .iter()
+        .position(|candidate| *candidate == name)
+        .map(|index| index as u8)
+}
+
+fn parse_key_action(token: &str, names: &Names) -> TextResult<KeyAction> {
+    let invalid = || TextError::invalid("key action", token);
+    Ok(match token.split_once(':') {
+        _ if token == "_" => KeyAction::None,
+        Some(("layer", layer)) => {
+            let (layer, mode) = match layer.split_once(':') {
+                Some((layer, mode)) => (
+                    layer,
+                    find_value(SWITCH_LAYER_MODES, mode).ok_or_else(invalid)?,
+                ),
+                None => (layer, SwitchLayerMode::HoldAndDoubleTapToggle),
+            };
+            KeyAction::SwitchLayer(layer_id(layer).ok_or_else(invalid)?, mode)
+        }
+        Some(("keymap", keymap)) => {
+            KeyAction::SwitchKeymap(resolve(&names.keymaps, keymap).ok_or_else(invalid)?)
+        }
+        Some(("mouse", action)) => {
+            KeyAction::MouseAction(find_value(MOUSE_ACTIONS, action).ok_or_else(invalid)?)
+        }
+        Some(("macro", macro_)) => {
+            KeyAction::PlayMacroAction(resolve(&names.macros, macro_).ok_or_else(invalid)?)
+        }
+        _ => KeyAction::Keystroke(parse_keystroke(token).ok_or_else(invalid)?),
+    })
+}
+
+fn format_key_action(action: &KeyAction, names: &Names) -> TextResult<String> {
+    Ok(match action {
+        KeyAction::None => "_".to_string(),
+        KeyAction::Keystroke(keystroke) => format_keystroke(keystroke),
+        KeyAction::SwitchLayer(layer, mode) => match mode {
+            SwitchLayerMode::HoldAndDoubleTapToggle => format!("layer:{}", layer_name(*layer)),
+            _ => format!(
+                "layer:{}:{}",
+                layer_name(*layer),
+                name_of(SWITCH_LAYER_MODES, *mode)
+            ),
+        },
+        KeyAction::SwitchKeymap(index) => format!("keymap:{}", reference(&names.keymaps, *index)),
+        KeyAction::MouseAction(action) => format!("mouse:{}", name_of(MOUSE_ACTIONS, *action)),
+        KeyAction::PlayMacroAction(index) => format!("macro:{}", reference(&names.macros, *index)),
+    })
+}
+
+/// Parses a key name, or a keystroke type and a scancode or `-` for none.
+fn parse_key(name: &str) -> Option<(KeystrokeType, Option<Scancode>)> {
+    if let Some((atype, code)) = name.split_once(':') {
+        let atype = find_value(KEYSTROKE_TYPES, atype)?;
+        if code == "-" {
+            return Some((atype, None));
+        }
+        let code = match code.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+            None => code.parse().ok()?,
+        };
+        return Some((atype, Some(Scancode(code))));
+    }
+    scancodes::lookup(name).map(|(atype, code)| (atype, Some(Scancode(code))))
+}
+
+fn format_key(atype: KeystrokeType, scancode: Scancode) -> String {
+    match scancode.name(atype) {
+        Some(name) if scancodes::lookup(name) == Some((atype, scancode.0)) => name.to_string(),
+        _ => format!("{}:{:#04x}", name_of(KEYSTROKE_TYPES, atype), scancode.0),
+    }
+}
+
+/// Stands for modifiers that are stored but empty.
+const NO_MODIFIERS: &str = "NoMods";
+
+fn parse_modifier(name: &str) -> Option<Modifiers> {
+    (0..8)
+        .map(|bit| Modifiers::from_bits_retain(1 << bit))
+        .find(|modifier| modifier.to_string() == name)
+}
+
+fn parse_secondary_role(name: &str) -> Option<SecondaryRole> {
+    (0..=u8::MAX)
+        .filter_map(|role| SecondaryRole::try_from(role).ok())
+        .find(|role| role.to_string() == name)
+}
+
+/// Parses `[modifier+...][key][/role]`, where a lone `-` stands for a
+/// keystroke without key or modifiers.
+fn parse_keystroke(token: &str) -> Option<Keystroke> {
+    let (keys, secondary_role) = match token.split_once('/') {
+        Some((keys, role)) => (keys, Some(parse_secondary_role(role)?)),
+        None => (token, None),
+    };
+    let mut modifiers = None;
+    let mut key = None;
+    if keys != "-" {
+        for part in keys.split('+') {
+            if part == NO_MODIFIERS {
+                modifiers.get_or_insert(Modifiers::empty());
+            } else if let Some(modifier) = parse_modifier(part) {
+                *modifiers.get_or_insert(Modifiers::empty()) |= modifier;
+            } else if key.is_none() {
+                key = Some(parse_key(part)?);
+            } else {
+                return None;
+            }
+        }
+    }
+    let (atype, scancode) = key.unwrap_or((KeystrokeType::Basic, None));
+    Some(Keystroke {
+        atype,
+        scancode,
+        modifiers,
+        secondary_role,
+    })
+}
+
+fn format_keystroke(keystroke: &Keystroke) -> String {
+    let mut parts = vec![];
+    match keystroke.modifiers {
+        Some(modifiers) if modifiers.is_empty() => parts.push(NO_MODIFIERS.to_string()),
+        Some(modifiers) => parts.push(modifiers.to_string()),
+        None => {}
+    }
+    match keystroke.scancode {
+        Some(scancode) => parts.push(format_key(keystroke.atype, scancode)),
+        None if keystroke.atype != KeystrokeType::Basic => {
+            parts.push(format!("{}:-", name_of(KEYSTROKE_TYPES, keystroke.atype)))
+        }
+        None => {}
+    }
+    let mut token = if parts.is_empty() {
+        "-".to_string()
+    } else {
+        parts.join("+")
+    };
+    if let Some(role) = keystroke.secondary_role {
+        token.push('/');
+        token.push_str(&role.to_string());
+    }
+    token
+}
+
+fn compile_macro(macro_: &TextMacro) -> TextResult<Macro> {
+    Ok(Macro {
+        looped: macro_.looped,
+        private: macro_.private,
+        name: macro_.name.clone(),
+        actions: macro_
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(i, action)| {
+                parse_macro_action(action).ok_or_else(|| {
+                    TextError::invalid("macro action", action).context(PathSegment::Action(i))
+                })
+            })
+            .try_collect()?,
+    })
+}
+
+fn decompile_macro(macro_: &Macro) -> TextMacro {
+    TextMacro {
+        name: macro_.name.clone(),
+        looped: macro_.looped,
+        private: macro_.private,
+        actions: macro_.actions.iter().map(format_macro_action).collect(),
+    }
+}
+
+fn parse_macro_action(action: &str) -> Option<MacroAction> {
+    let (verb, rest) = action.split_once(' ').unwrap_or((action, ""));
+    let pair = |rest: &str| {
+        let (x, y) = rest.split_once(' ')?;
+        Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+    };
+    Some(match verb {
+        "move" => {
+            let (x, y) = pair(rest)?;
+            MacroAction::MoveMouse { x, y }
+        }
+        "scroll" => {
+            let (x, y) = pair(rest)?;
+            MacroAction::ScrollMouse { x, y }
+        }
+        "delay" => MacroAction::Delay(rest.trim().parse().ok()?),
+        "text" => MacroAction::Text(rest.to_string()),
+        "command" => MacroAction::Command(rest.to_string()),
+        _ => {
+            let action = find_value(MACRO_SUB_ACTIONS, verb)?;
+            match rest.trim().strip_prefix("buttons ") {
+                Some(buttons) => MacroAction::MouseButton {
+                    action,
+                    buttons: buttons.trim().parse().ok()?,
+                },
+                None => {
+                    let keystroke = parse_keystroke(rest.trim())?;
+                    if keystroke.secondary_role.is_some() {
+                        return None;
+                    }
+                    MacroAction::Key {
+                        action,
+                        atype: keystroke.atype,
+                        scancode: keystroke.scancode,
+                        modifiers: keystroke.modifiers,
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn format_macro_action(action: &MacroAction) -> String {
+    match action {
+        MacroAction::Key {
+            action,
+            atype,
+            scancode,
+            modifiers,
+        } => format!(
+            "{} {}",
+            name_of(MACRO_SUB_ACTIONS, *action),
+            format_keystroke(&Keystroke {
+                atype: *atype,
+                scancode: *scancode,
+                modifiers: *modifiers,
+                secondary_role: None,
+            })
+        ),
+        MacroAction::MouseButton { action, buttons } => {
+            format!(
+                "{} buttons {}",
+                name_of(MACRO_SUB_ACTIONS, *action),
+                buttons
+            )
+        }
+        MacroAction::MoveMouse { x, y } => format!("move {} {}", x, y),
+        MacroAction::ScrollMouse { x, y } => format!("scroll {} {}", x, y),
+        MacroAction::Delay(delay) => format!("delay {}", delay),
+        MacroAction::Text(text) => format!("text {}", text),
+        MacroAction::Command(command) => format!("command {}", command),
+    }
+}
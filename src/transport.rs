@@ -0,0 +1,92 @@
+use crate::{
+    consts,
+    device::{DeviceError, DeviceResult},
+};
+use hidapi::HidDevice;
+use std::{cell::RefCell, collections::VecDeque, time::Duration};
+
+pub trait Transport {
+    fn send(&self, report: &[u8]) -> DeviceResult<()>;
+    fn receive(&self, timeout: Duration) -> DeviceResult<Vec<u8>>;
+}
+
+impl<T: Transport + ?Sized> Transport for &T {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        (**self).send(report)
+    }
+    fn receive(&self, timeout: Duration) -> DeviceResult<Vec<u8>> {
+        (**self).receive(timeout)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        (**self).send(report)
+    }
+    fn receive(&self, timeout: Duration) -> DeviceResult<Vec<u8>> {
+        (**self).receive(timeout)
+    }
+}
+
+impl Transport for HidDevice {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        // prefix with report id 0, the UHK does not use numbered reports
+        let mut buf = Vec::with_capacity(report.len() + 1);
+        buf.push(0x0);
+        buf.extend_from_slice(report);
+        self.write(&buf)?;
+        Ok(())
+    }
+    fn receive(&self, timeout: Duration) -> DeviceResult<Vec<u8>> {
+        let mut buf = vec![0u8; consts::MAX_PAYLOAD_SIZE];
+        let read = self.read_timeout(&mut buf, timeout.as_millis() as i32)?;
+        // hidapi reports a timeout as an empty read
+        if read == 0 {
+            return Err(DeviceError::Timeout);
+        }
+        buf.truncate(read);
+        Ok(buf)
+    }
+}
+
+/// In-memory transport answering a fixed script of request/response pairs.
+/// Responses are delivered exactly as scripted, so one shorter than a report
+/// stands for a short read.
+#[derive(Default)]
+pub struct ScriptedTransport {
+    script: RefCell<VecDeque<(Vec<u8>, Vec<u8>)>>,
+    pending: RefCell<VecDeque<Vec<u8>>>,
+}
+
+impl ScriptedTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn expect(self, request: &[u8], response: &[u8]) -> Self {
+        self.script
+            .borrow_mut()
+            .push_back((request.to_vec(), response.to_vec()));
+        self
+    }
+    pub fn is_done(&self) -> bool {
+        self.script.borrow().is_empty() && self.pending.borrow().is_empty()
+    }
+}
+
+impl Transport for ScriptedTransport {
+    fn send(&self, report: &[u8]) -> DeviceResult<()> {
+        match self.script.borrow_mut().pop_front() {
+            Some((request, response)) if request == report => {
+                self.pending.borrow_mut().push_back(response);
+                Ok(())
+            }
+            _ => Err(DeviceError::UnexpectedReport(report.to_vec())),
+        }
+    }
+    fn receive(&self, _timeout: Duration) -> DeviceResult<Vec<u8>> {
+        self.pending
+            .borrow_mut()
+            .pop_front()
+            .ok_or(DeviceError::Timeout)
+    }
+}
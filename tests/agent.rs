@@ -0,0 +1,118 @@
+#![cfg(feature = "serde")]
+use uhkctl::{
+    agent::{self, AgentError},
+    config::{KeyAction, MacroAction, Modifiers, UserConfig},
+    consts::{KeystrokeType, MouseActionParam, SecondaryRole, SwitchLayerMode},
+    device::{UhkCursor, UhkWriter},
+};
+
+const USER_CONFIG_JSON: &str = include_str!("fixtures/user-config.json");
+/// A document as saved by Agent itself.
+const AGENT_EXPORT: &str = include_str!("fixtures/agent-user-config.json");
+const USER_CONFIG_V5: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+
+fn serialize(config: &UserConfig) -> Vec<u8> {
+    let mut writer = UhkWriter::new();
+    config.serialize(&mut writer).unwrap();
+    writer.into_inner()
+}
+
+#[test]
+fn json_to_binary() {
+    let config = agent::from_json(USER_CONFIG_JSON).unwrap();
+    assert_eq!(serialize(&config), USER_CONFIG_V5);
+}
+
+#[test]
+fn agent_export() {
+    let config = agent::from_json(AGENT_EXPORT).unwrap();
+    assert_eq!(config.name, "Office UHK");
+    let [qwerty, dvorak] = &config.keymaps[..] else {
+        panic!("expected two keymaps");
+    };
+    let base = &qwerty.layers[0].modules;
+    assert!(matches!(
+        &base[0].key_actions[3],
+        KeyAction::Keystroke(key)
+            if key.secondary_role == Some(SecondaryRole::LeftCtrl) && key.modifiers.is_none()
+    ));
+    assert!(matches!(
+        base[0].key_actions[4],
+        KeyAction::SwitchLayer(1, SwitchLayerMode::HoldAndDoubleTapToggle)
+    ));
+    assert!(matches!(
+        base[0].key_actions[5],
+        KeyAction::SwitchLayer(3, SwitchLayerMode::Toggle)
+    ));
+    assert!(matches!(
+        &base[1].key_actions[1],
+        KeyAction::Keystroke(key)
+            if key.scancode.is_none() && key.modifiers == Some(Modifiers::LSHIFT)
+    ));
+    assert!(matches!(
+        base[1].key_actions[3],
+        KeyAction::PlayMacroAction(0)
+    ));
+    assert!(matches!(
+        base[2].key_actions[0],
+        KeyAction::MouseAction(MouseActionParam::LeftClick)
+    ));
+    assert!(matches!(base[2].key_actions[2], KeyAction::SwitchKeymap(1)));
+    assert!(matches!(
+        &qwerty.layers[1].modules[0].key_actions[1],
+        KeyAction::Keystroke(key) if key.atype == KeystrokeType::LongMedia
+    ));
+    assert!(matches!(
+        dvorak.layers[0].modules[0].key_actions[1],
+        KeyAction::SwitchKeymap(0)
+    ));
+    assert_eq!(config.macros[0].name, "Sign off");
+    assert!(matches!(
+        config.macros[0].actions[..],
+        [
+            MacroAction::Key { .. },
+            MacroAction::Delay(50),
+            MacroAction::Text(_),
+            MacroAction::MouseButton { buttons: 1, .. },
+            MacroAction::MoveMouse { x: -10, y: 5 },
+        ]
+    ));
+}
+
+#[test]
+fn round_trip() {
+    let config = agent::from_json(AGENT_EXPORT).unwrap();
+    let config = UserConfig::deserialize(&mut UhkCursor::new(serialize(&config))).unwrap();
+    let json = agent::to_json(&config).unwrap();
+    let expected: serde_json::Value = serde_json::from_str(AGENT_EXPORT).unwrap();
+    let actual: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn unknown_names_are_rejected() {
+    let json = USER_CONFIG_JSON.replacen(
+        r#""secondaryRoleAction": "leftCtrl""#,
+        r#""secondaryRoleAction": "leftHyper""#,
+        1,
+    );
+    assert_ne!(json, USER_CONFIG_JSON);
+    assert!(matches!(
+        agent::from_json(&json),
+        Err(AgentError::UnknownName("secondary role", name)) if name == "leftHyper"
+    ));
+}
+
+#[test]
+fn unknown_keymap_references_are_rejected() {
+    let json = USER_CONFIG_JSON.replacen(
+        r#""keymapAbbreviation": "DVO""#,
+        r#""keymapAbbreviation": "COL""#,
+        1,
+    );
+    assert_ne!(json, USER_CONFIG_JSON);
+    assert!(matches!(
+        agent::from_json(&json),
+        Err(AgentError::UnknownKeymap(abbr)) if abbr == "COL"
+    ));
+}
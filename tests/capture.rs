@@ -0,0 +1,84 @@
+use std::time::Duration;
+use uhkctl::{
+    capture::{read_capture, Direction, Record, Recorder, Replay},
+    consts::UsbCommand,
+    device::{Device, DeviceError},
+    transport::{ScriptedTransport, Transport},
+};
+
+fn switch_keymap(abbr: &str) -> Vec<u8> {
+    let mut report = vec![UsbCommand::SwitchKeymap.into(), abbr.len() as u8];
+    report.extend_from_slice(abbr.as_bytes());
+    report
+}
+
+#[test]
+fn line_format() {
+    let record = Record {
+        timestamp: Duration::from_micros(1500),
+        direction: Direction::Out,
+        data: vec![0x12, 0x03, 0xff],
+    };
+    assert_eq!(record.to_line(), "1500 > 12 03 ff");
+    assert_eq!(Record::from_line("1500 > 12 03 ff"), Some(record));
+    assert_eq!(Record::from_line("1500 ? 12"), None);
+    assert_eq!(Record::from_line("1500 < 1g"), None);
+}
+
+#[test]
+fn malformed_lines_are_reported() {
+    let capture = "# uhkctl capture v1\n10 > 12\n\n20 < zz\n";
+    assert!(matches!(
+        read_capture(capture.as_bytes()),
+        Err(DeviceError::Capture(4))
+    ));
+}
+
+#[test]
+fn recorder_round_trip() {
+    let transport = ScriptedTransport::new().expect(&switch_keymap("QWR"), &[0, 0, 0]);
+    let recorder = Recorder::new(&transport, vec![]).unwrap();
+    let device = Device::open(recorder);
+    device.switch_keymap("QWR").unwrap();
+    let (_, out) = device.into_inner().into_inner();
+    let capture = String::from_utf8(out).unwrap();
+    assert!(capture.starts_with("# uhkctl capture v1\n"));
+    let records = read_capture(capture.as_bytes()).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].direction, Direction::Out);
+    assert_eq!(records[0].data, switch_keymap("QWR"));
+    assert_eq!(records[1].direction, Direction::In);
+    assert_eq!(records[1].data, [0, 0, 0]);
+    assert!(records[0].timestamp <= records[1].timestamp);
+
+    let replay = Replay::new(records);
+    Device::open(&replay).switch_keymap("QWR").unwrap();
+    assert!(replay.is_done());
+}
+
+#[test]
+fn replay_rejects_other_reports() {
+    let records = vec![
+        Record {
+            timestamp: Duration::ZERO,
+            direction: Direction::Out,
+            data: switch_keymap("QWR"),
+        },
+        Record {
+            timestamp: Duration::from_millis(1),
+            direction: Direction::In,
+            data: vec![0],
+        },
+    ];
+    let replay = Replay::new(records);
+    assert!(matches!(
+        Device::open(&replay).switch_keymap("DVO"),
+        Err(DeviceError::UnexpectedReport(report)) if report == switch_keymap("DVO")
+    ));
+    // nothing was consumed, and no response is due before the report
+    assert!(matches!(
+        replay.receive(Duration::ZERO),
+        Err(DeviceError::Timeout)
+    ));
+    assert!(!replay.is_done());
+}
@@ -0,0 +1,295 @@
+use uhkctl::{
+    consts::{ConfigBufferId, DevicePropertyIds, EepromOperation, UsbCommand, UsbVariables},
+    device::{Device, DeviceError},
+    emulator::Emulator,
+    transport::ScriptedTransport,
+};
+
+const GET_CONFIG_SIZES: [u8; 2] = [
+    UsbCommand::GetProperty as u8,
+    DevicePropertyIds::ConfigSizes as u8,
+];
+
+/// Response to GetProperty ConfigSizes.
+fn config_sizes(hardware: u16, user: u16) -> Vec<u8> {
+    let mut response = vec![0];
+    response.extend_from_slice(&hardware.to_le_bytes());
+    response.extend_from_slice(&user.to_le_bytes());
+    response
+}
+
+const GET_PROTOCOL_VERSIONS: [u8; 2] = [
+    UsbCommand::GetProperty as u8,
+    DevicePropertyIds::ProtocolVersions as u8,
+];
+
+/// Response to GetProperty ProtocolVersions for data model 5.0.0.
+fn protocol_versions() -> Vec<u8> {
+    let mut response = vec![0];
+    for version in [[9, 0, 0], [5, 0, 0], [1, 2, 0], [4, 0, 0], [3, 0, 0]] {
+        for part in version {
+            response.extend_from_slice(&u16::to_le_bytes(part));
+        }
+    }
+    response
+}
+
+/// A user config of `length` bytes starting with data model 5.0.0.
+fn user_config(length: usize) -> Vec<u8> {
+    let mut config: Vec<u8> = (0..length).map(|i| i as u8).collect();
+    config[..6].copy_from_slice(&[5, 0, 0, 0, 0, 0]);
+    config
+}
+
+fn write_staging_user_config(data: &[u8], offset: u16) -> Vec<u8> {
+    let offset = offset.to_le_bytes();
+    let mut report = vec![
+        UsbCommand::WriteStagingUserConfig.into(),
+        data.len() as u8,
+        offset[0],
+        offset[1],
+    ];
+    report.extend_from_slice(data);
+    report
+}
+
+/// The commands of an upload up to applying the config.
+fn upload_script(config: &[u8]) -> ScriptedTransport {
+    let transport = ScriptedTransport::new()
+        .expect(&GET_PROTOCOL_VERSIONS, &protocol_versions())
+        .expect(&GET_CONFIG_SIZES, &config_sizes(64, 32704));
+    config
+        .chunks(60)
+        .enumerate()
+        .fold(transport, |transport, (i, chunk)| {
+            transport.expect(&write_staging_user_config(chunk, i as u16 * 60), &[0])
+        })
+}
+
+fn read_config(buffer: ConfigBufferId, length: u8, offset: u16) -> Vec<u8> {
+    let offset = offset.to_le_bytes();
+    vec![
+        UsbCommand::ReadConfig.into(),
+        buffer.into(),
+        length,
+        offset[0],
+        offset[1],
+    ]
+}
+
+/// Successful ReadConfig response carrying `data`.
+fn chunk(data: &[u8]) -> Vec<u8> {
+    let mut response = vec![0];
+    response.extend_from_slice(data);
+    response
+}
+
+#[test]
+fn config_sizes_are_little_endian() {
+    let transport = ScriptedTransport::new().expect(&GET_CONFIG_SIZES, &config_sizes(64, 32704));
+    let device = Device::open(&transport);
+    assert_eq!(device.get_config_size().unwrap(), (64, 32704));
+    assert!(transport.is_done());
+}
+
+#[test]
+fn load_config_requests_exact_chunks() {
+    let data: Vec<u8> = (0..70).collect();
+    let buffer = ConfigBufferId::HardwareConfig;
+    let transport = ScriptedTransport::new()
+        .expect(&GET_CONFIG_SIZES, &config_sizes(70, 0))
+        .expect(&read_config(buffer, 63, 0), &chunk(&data[..63]))
+        .expect(&read_config(buffer, 7, 63), &chunk(&data[63..]));
+    let device = Device::open(&transport);
+    assert_eq!(device.load_config(buffer).unwrap(), data);
+    assert!(transport.is_done());
+}
+
+#[test]
+fn load_config_ignores_report_padding() {
+    let buffer = ConfigBufferId::ValidatedUserConfig;
+    let mut response = chunk(&[1, 2, 3]);
+    response.resize(64, 0xee);
+    let transport = ScriptedTransport::new()
+        .expect(&GET_CONFIG_SIZES, &config_sizes(64, 3))
+        .expect(&read_config(buffer, 3, 0), &response);
+    let device = Device::open(&transport);
+    assert_eq!(device.load_config(buffer).unwrap(), [1, 2, 3]);
+}
+
+#[test]
+fn load_config_fails_on_status() {
+    let buffer = ConfigBufferId::HardwareConfig;
+    let transport = ScriptedTransport::new()
+        .expect(&GET_CONFIG_SIZES, &config_sizes(10, 0))
+        .expect(&read_config(buffer, 10, 0), &[4]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.load_config(buffer),
+        Err(DeviceError::Status(UsbCommand::ReadConfig, 4))
+    ));
+}
+
+#[test]
+fn load_config_detects_short_reads() {
+    let buffer = ConfigBufferId::HardwareConfig;
+    let transport = ScriptedTransport::new()
+        .expect(&GET_CONFIG_SIZES, &config_sizes(10, 0))
+        .expect(&read_config(buffer, 10, 0), &chunk(&[0; 6]));
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.load_config(buffer),
+        Err(DeviceError::ShortResponse {
+            command: UsbCommand::ReadConfig,
+            expected: 11,
+            actual: 7,
+        })
+    ));
+}
+
+#[test]
+fn config_sizes_detect_short_reads() {
+    let transport = ScriptedTransport::new().expect(&GET_CONFIG_SIZES, &[0, 64, 0]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.get_config_size(),
+        Err(DeviceError::ShortResponse { .. })
+    ));
+}
+
+#[test]
+fn load_config_reads_whole_emulated_buffers() {
+    let hardware: Vec<u8> = (0..64).collect();
+    let user: Vec<u8> = (0..=255).cycle().take(32704).collect();
+    let device = Device::open(Emulator::new(&hardware, &user));
+    assert_eq!(
+        device.load_config(ConfigBufferId::HardwareConfig).unwrap(),
+        hardware
+    );
+    assert_eq!(
+        device
+            .load_config(ConfigBufferId::ValidatedUserConfig)
+            .unwrap(),
+        user
+    );
+}
+
+#[test]
+fn upload_writes_exact_chunks() {
+    let config = user_config(130);
+    let transport = upload_script(&config)
+        .expect(&[UsbCommand::ApplyConfig.into()], &[0])
+        .expect(
+            &[
+                UsbCommand::LaunchEepromTransfer.into(),
+                EepromOperation::Write.into(),
+                ConfigBufferId::ValidatedUserConfig.into(),
+            ],
+            &[0],
+        )
+        .expect(&[UsbCommand::GetDeviceState.into()], &[0, 0, 1, 1, 0, 0, 0]);
+    let device = Device::open(&transport);
+    device.upload_user_config(&config).unwrap();
+    assert!(transport.is_done());
+}
+
+#[test]
+fn upload_reports_where_the_config_was_rejected() {
+    let config = user_config(70);
+    let transport =
+        upload_script(&config).expect(&[UsbCommand::ApplyConfig.into()], &[3, 0x34, 0x12]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.upload_user_config(&config),
+        Err(DeviceError::ApplyConfig {
+            code: 3,
+            offset: 0x1234
+        })
+    ));
+    // nothing is written to the EEPROM
+    assert!(transport.is_done());
+}
+
+#[test]
+fn rejected_config_without_offset_is_a_short_response() {
+    let config = user_config(10);
+    let transport = upload_script(&config).expect(&[UsbCommand::ApplyConfig.into()], &[3]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.upload_user_config(&config),
+        Err(DeviceError::ShortResponse {
+            command: UsbCommand::ApplyConfig,
+            expected: 3,
+            actual: 1,
+        })
+    ));
+}
+
+#[test]
+fn device_state_detects_short_reads() {
+    let transport =
+        ScriptedTransport::new().expect(&[UsbCommand::GetDeviceState.into()], &[0, 0, 1]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.state(),
+        Err(DeviceError::ShortResponse {
+            command: UsbCommand::GetDeviceState,
+            expected: 7,
+            actual: 3,
+        })
+    ));
+}
+
+#[test]
+fn get_variable_detects_short_reads() {
+    let var = UsbVariables::DebounceTimePress;
+    let transport =
+        ScriptedTransport::new().expect(&[UsbCommand::GetVariable.into(), var.into()], &[0]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.get_variable(var),
+        Err(DeviceError::ShortResponse {
+            command: UsbCommand::GetVariable,
+            expected: 2,
+            actual: 1,
+        })
+    ));
+}
+
+#[test]
+fn properties_detect_short_reads() {
+    let property = |id: DevicePropertyIds| [UsbCommand::GetProperty.into(), id.into()];
+    let transport = ScriptedTransport::new()
+        .expect(&GET_PROTOCOL_VERSIONS, &protocol_versions()[..7])
+        .expect(
+            &property(DevicePropertyIds::I2cBaudRate),
+            &[0, 0, 0xa0, 0x86],
+        )
+        .expect(&property(DevicePropertyIds::CurrentKbootCommand), &[0])
+        .expect(&property(DevicePropertyIds::GitTag), &[0])
+        .expect(&property(DevicePropertyIds::GitRepo), &[]);
+    let device = Device::open(&transport);
+    let short = |result: Result<_, DeviceError>| match result {
+        Err(DeviceError::ShortResponse {
+            command: UsbCommand::GetProperty,
+            expected,
+            actual,
+        }) => (expected, actual),
+        Err(err) => panic!("expected a short response, got {:?}", err),
+        Ok(_) => panic!("expected a short response"),
+    };
+    assert_eq!(short(device.protocol_versions().map(|_| ())), (31, 7));
+    assert_eq!(short(device.i2c_baud_rate().map(|_| ())), (10, 4));
+    assert_eq!(short(device.current_kboot_command().map(|_| ())), (2, 1));
+    assert_eq!(short(device.git_tag().map(|_| ())), (2, 1));
+    // an empty report lacks even the status
+    assert!(matches!(
+        device.git_repo(),
+        Err(DeviceError::ShortResponse {
+            expected: 1,
+            actual: 0,
+            ..
+        })
+    ));
+    assert!(transport.is_done());
+}
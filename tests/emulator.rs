@@ -0,0 +1,346 @@
+use std::time::{Duration, Instant};
+use uhkctl::{
+    capture::{read_capture, Direction, Recorder},
+    config::UserConfig,
+    consts::{
+        ConfigBufferId, KbootCommands, ModulePropertyId, ModuleSlots, SwitchKeymapStatus,
+        UsbCommand, UsbStatusCode, UsbVariables,
+    },
+    device::{Device, DeviceError, I2cBaudRate, ProtocolVersions, UhkCursor, Version},
+    emulator::{Emulator, USER_CONFIG_SIZE},
+};
+
+const USER_CONFIG: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+
+fn emulator(user_config: &[u8]) -> Emulator {
+    Emulator::new(&[0; 64], user_config).with_eeprom_busy_time(Duration::ZERO)
+}
+
+#[test]
+fn backup() {
+    let emulator = emulator(USER_CONFIG);
+    let device = Device::open(&emulator);
+    let mut data = device
+        .load_config(ConfigBufferId::ValidatedUserConfig)
+        .unwrap();
+    let config = UserConfig::deserialize(&mut UhkCursor::new(data.clone())).unwrap();
+    data.truncate(config.length as usize);
+    assert_eq!(data, USER_CONFIG);
+    assert_eq!(config.keymaps[0].abbr, "QWR");
+}
+
+#[test]
+fn backup_in_whole_chunks() {
+    let emulator = emulator(USER_CONFIG);
+    // two full reports after the status byte, and no trailing partial one
+    let hardware_config: Vec<u8> = (0..126).collect();
+    emulator.update_state(|state| state.hardware_config = hardware_config.clone());
+    let device = Device::open(&emulator);
+    assert_eq!(
+        device.load_config(ConfigBufferId::HardwareConfig).unwrap(),
+        hardware_config
+    );
+}
+
+#[test]
+fn restore() {
+    let emulator = emulator(&[]);
+    let device = Device::open(&emulator);
+    device.upload_user_config(USER_CONFIG).unwrap();
+    let state = emulator.state();
+    assert_eq!(
+        &state.validated_user_config[..USER_CONFIG.len()],
+        USER_CONFIG
+    );
+    assert_eq!(&state.eeprom_user_config[..USER_CONFIG.len()], USER_CONFIG);
+    assert!(!device.state().unwrap().eeprom_busy);
+}
+
+#[test]
+fn restore_rejects_a_broken_config() {
+    let emulator = emulator(USER_CONFIG);
+    let device = Device::open(&emulator);
+    // the first key action follows the QWR keymap header and the counts and
+    // ids of its first layer and module
+    let keymap = USER_CONFIG
+        .windows(4)
+        .position(|window| window == b"\x03QWR")
+        .unwrap();
+    let description = b"The standard layout.";
+    let action = keymap + 4 + 1 + 7 + 1 + description.len() + 5;
+    let mut broken = USER_CONFIG.to_vec();
+    broken[action] = 0xfe;
+    assert!(matches!(
+        device.upload_user_config(&broken),
+        Err(DeviceError::ApplyConfig { code: 1, offset }) if offset as usize == action + 1
+    ));
+    let state = emulator.state();
+    assert_eq!(
+        &state.validated_user_config[..USER_CONFIG.len()],
+        USER_CONFIG
+    );
+}
+
+#[test]
+fn restore_rejects_an_oversized_config() {
+    let emulator = emulator(USER_CONFIG);
+    let device = Device::open(&emulator);
+    // large enough for chunk offsets to wrap around
+    let mut oversized = USER_CONFIG.to_vec();
+    oversized.resize(0x10000 + USER_CONFIG.len(), 0);
+    assert!(matches!(
+        device.upload_user_config(&oversized),
+        Err(DeviceError::ConfigTooLarge {
+            size,
+            capacity: USER_CONFIG_SIZE,
+        }) if size == oversized.len()
+    ));
+    // nothing was written
+    let state = emulator.state();
+    assert_eq!(state.staging_user_config, state.validated_user_config);
+}
+
+/// Uploads `config` to an emulator whose firmware speaks `data_model`,
+/// returning the result and whether the config was applied.
+fn upload_to(data_model: Version, config: &[u8]) -> (Result<(), DeviceError>, bool) {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| state.data_model_version = data_model);
+    let device = Device::open(Recorder::new(&emulator, vec![]).unwrap());
+    let result = device.upload_user_config(config);
+    let (_, capture) = device.into_inner().into_inner();
+    let applied = read_capture(&capture[..]).unwrap().iter().any(|record| {
+        record.direction == Direction::Out && record.data[0] == u8::from(UsbCommand::ApplyConfig)
+    });
+    (result, applied)
+}
+
+#[test]
+fn restore_rejects_unknown_data_models() {
+    let (result, applied) = upload_to(Version::new(7, 0, 0), USER_CONFIG);
+    assert!(matches!(
+        result,
+        Err(DeviceError::UnsupportedDataModel(version)) if version == Version::new(7, 0, 0)
+    ));
+    assert!(!applied);
+}
+
+#[test]
+fn restore_rejects_configs_of_another_data_model() {
+    let (result, applied) = upload_to(Version::new(6, 0, 0), USER_CONFIG);
+    assert!(matches!(
+        result,
+        Err(DeviceError::DataModelMismatch { config, device })
+            if config == Version::new(5, 0, 0) && device == Version::new(6, 0, 0)
+    ));
+    assert!(!applied);
+
+    // newer minor versions than the firmware's are refused too
+    let mut config = USER_CONFIG.to_vec();
+    config[2] = 1;
+    let (result, applied) = upload_to(Version::new(5, 0, 0), &config);
+    assert!(matches!(result, Err(DeviceError::DataModelMismatch { .. })));
+    assert!(!applied);
+}
+
+#[test]
+fn switch_keymap() {
+    let emulator = emulator(USER_CONFIG);
+    let device = Device::open(&emulator);
+    device.switch_keymap("DVO").unwrap();
+    assert_eq!(emulator.state().current_keymap.as_deref(), Some("DVO"));
+    assert!(device.switch_keymap("").is_err());
+
+    let status: u8 = SwitchKeymapStatus::InvalidAbbreviation.into();
+    assert!(matches!(
+        device.switch_keymap("COL"),
+        Err(DeviceError::Status(_, code)) if code == status
+    ));
+    assert_eq!(emulator.state().current_keymap.as_deref(), Some("DVO"));
+}
+
+#[test]
+fn variables() {
+    let emulator = emulator(USER_CONFIG);
+    let device = Device::open(&emulator);
+    device
+        .set_variable(UsbVariables::DebounceTimePress, 7)
+        .unwrap();
+    assert_eq!(
+        device
+            .get_variable(UsbVariables::DebounceTimePress)
+            .unwrap(),
+        7
+    );
+    assert_eq!(emulator.state().variables[2], 7);
+}
+
+#[test]
+fn device_state() {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| {
+        state.halves_merged = false;
+        state.active_layer = 2;
+        state.active_layer_toggled = true;
+        state.right_module_slot = ModuleSlots::TrackballRight;
+    });
+    let device = Device::open(&emulator);
+    let state = device.state().unwrap();
+    assert!(!state.halves_merged);
+    assert!(state.left_half_connected);
+    assert_eq!(state.active_layer, 2);
+    assert!(state.active_layer_toggled);
+    assert_eq!(state.left_module_slot, ModuleSlots::NoModule);
+    assert_eq!(state.right_module_slot, ModuleSlots::TrackballRight);
+}
+
+#[test]
+fn device_state_while_writing_the_eeprom() {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| {
+        state.eeprom_busy_until = Some(Instant::now() + Duration::from_secs(60));
+        state.left_half_connected = false;
+    });
+    let device = Device::open(&emulator);
+    let state = device.state().unwrap();
+    assert!(state.eeprom_busy);
+    assert!(!state.left_half_connected);
+    assert!(!state.active_layer_toggled);
+}
+
+#[test]
+fn uptime() {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| state.booted = Instant::now() - Duration::from_secs(5));
+    let device = Device::open(&emulator);
+    let uptime = device.uptime().unwrap();
+    assert!(uptime >= Duration::from_secs(5) && uptime < Duration::from_secs(6));
+}
+
+#[test]
+fn module_properties() {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| state.right_module_slot = ModuleSlots::TrackballRight);
+    let device = Device::open(&emulator);
+    let mut cursor = device
+        .get_module_property(
+            ModuleSlots::TrackballRight,
+            ModulePropertyId::ProtocolVersions,
+        )
+        .unwrap();
+    assert_eq!(
+        Version::deserialize(&mut cursor).unwrap(),
+        Version::new(4, 0, 0)
+    );
+    assert_eq!(
+        Version::deserialize(&mut cursor).unwrap(),
+        Version::new(9, 0, 0)
+    );
+    let tag = device
+        .get_module_property(ModuleSlots::LeftKeyboardHalf, ModulePropertyId::GitTag)
+        .unwrap()
+        .read_nul_terminated()
+        .unwrap();
+    assert_eq!(tag, "v9.0.0");
+
+    // modules not plugged in do not answer
+    let status: u8 = UsbStatusCode::InvalidCommand.into();
+    assert!(matches!(
+        device.get_module_property(ModuleSlots::KeyClusterLeft, ModulePropertyId::GitRepo),
+        Err(DeviceError::Status(_, code)) if code == status
+    ));
+}
+
+#[test]
+fn device_properties() {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| {
+        state.git_tag = "v9.1.0-3-gabcdef".to_string();
+        state.data_model_version = Version::new(6, 0, 0);
+    });
+    let device = Device::open(&emulator);
+    assert_eq!(device.device_protocol_version().unwrap(), 1);
+    assert_eq!(
+        device.protocol_versions().unwrap(),
+        ProtocolVersions {
+            firmware: Version::new(9, 0, 0),
+            data_model: Version::new(6, 0, 0),
+            usb_protocol: Version::new(1, 2, 0),
+            slave_protocol: Version::new(4, 0, 0),
+            module_protocol: Version::new(4, 0, 0),
+        }
+    );
+    assert_eq!(
+        device.i2c_baud_rate().unwrap(),
+        I2cBaudRate {
+            requested: 100_000,
+            actual: 100_000,
+        }
+    );
+    assert_eq!(device.git_tag().unwrap(), "v9.1.0-3-gabcdef");
+    assert_eq!(
+        device.git_repo().unwrap(),
+        "UltimateHackingKeyboard/firmware"
+    );
+    assert_eq!(device.current_kboot_command().unwrap(), KbootCommands::Idle);
+}
+
+#[cfg(feature = "text")]
+#[test]
+fn info_without_the_left_half() {
+    let emulator = emulator(USER_CONFIG);
+    emulator.update_state(|state| state.left_half_connected = false);
+    // what `uhkctl info` asks for, recorded to replay it to the command
+    let device = Device::open(Recorder::new(&emulator, vec![]).unwrap());
+    device.load_config(ConfigBufferId::HardwareConfig).unwrap();
+    device.uptime().unwrap();
+    device.device_protocol_version().unwrap();
+    device.protocol_versions().unwrap();
+    device.i2c_baud_rate().unwrap();
+    device.git_tag().unwrap();
+    device.git_repo().unwrap();
+    device.state().unwrap();
+    let (_, capture) = device.into_inner().into_inner();
+
+    let path = std::env::temp_dir().join(format!("uhkctl-info-{}.txt", std::process::id()));
+    std::fs::write(&path, capture).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_uhkctl"))
+        .arg("--replay")
+        .arg(&path)
+        .arg("info")
+        .output()
+        .unwrap();
+    std::fs::remove_file(path).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("right half git tag: v9.0.0\n"));
+    assert!(stdout.ends_with("left half: not connected\n"));
+}
+
+#[cfg(feature = "text")]
+#[test]
+fn binary_backup_of_an_unknown_data_model() {
+    // a config from newer firmware, padded past its length
+    let mut config = USER_CONFIG.to_vec();
+    config[0] = 7;
+    let emulator = emulator(&config);
+    emulator.update_state(|state| state.data_model_version = Version::new(7, 0, 0));
+    let device = Device::open(Recorder::new(&emulator, vec![]).unwrap());
+    device
+        .load_config(ConfigBufferId::ValidatedUserConfig)
+        .unwrap();
+    let (_, capture) = device.into_inner().into_inner();
+
+    let dir = std::env::temp_dir().join(format!("uhkctl-backup-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("capture.txt"), capture).unwrap();
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_uhkctl"))
+        .arg("--replay")
+        .arg(dir.join("capture.txt"))
+        .arg("backup")
+        .arg(dir.join("backup.bin"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(dir.join("backup.bin")).unwrap(), config);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,90 @@
+use uhkctl::{
+    config::HardwareConfig,
+    consts::ModuleId,
+    layout::{self, Layout},
+};
+
+const ANSI: Layout = Layout { iso: false };
+const ISO: Layout = Layout { iso: true };
+
+fn hardware(iso_mode: bool) -> HardwareConfig {
+    HardwareConfig {
+        signature: "FTY".to_string(),
+        major: 1,
+        minor: 0,
+        patch: 0,
+        brand_id: 0,
+        device_id: 1,
+        unique_id: 0,
+        vendor_mode: false,
+        iso_mode,
+    }
+}
+
+/// Number of keys in each row of a module under one layout.
+fn row_lengths(layout: Layout, module: ModuleId) -> Vec<usize> {
+    let mut rows = vec![0; layout.row_count(module.into())];
+    for key in layout.keys(module.into()) {
+        rows[key.row as usize] += 1;
+    }
+    rows
+}
+
+#[test]
+fn rows_per_module() {
+    let modules: [(ModuleId, &[usize], &[usize]); 6] = [
+        (
+            ModuleId::RightKeyboardHalf,
+            &[7, 8, 7, 6, 6],
+            &[7, 8, 7, 6, 6],
+        ),
+        (
+            ModuleId::LeftKeyboardHalf,
+            &[7, 6, 6, 6, 6],
+            &[7, 6, 6, 7, 6],
+        ),
+        (ModuleId::KeyClusterLeft, &[3], &[3]),
+        (ModuleId::TrackballRight, &[2], &[2]),
+        (ModuleId::TrackpointRight, &[2], &[2]),
+        (ModuleId::TouchpadRight, &[], &[]),
+    ];
+    for (module, ansi, iso) in modules {
+        assert_eq!(row_lengths(ANSI, module), ansi, "{:?} ANSI", module);
+        assert_eq!(row_lengths(ISO, module), iso, "{:?} ISO", module);
+        // key ids cover both layouts
+        assert_eq!(layout::rows(module.into()), iso, "{:?}", module);
+    }
+}
+
+#[test]
+fn key_ids_run_in_reading_order() {
+    for layout in [ANSI, ISO] {
+        for module in [ModuleId::RightKeyboardHalf, ModuleId::LeftKeyboardHalf] {
+            let keys = layout.keys(module.into());
+            assert!(keys
+                .windows(2)
+                .all(|pair| pair[0].id < pair[1].id && pair[0].row <= pair[1].row));
+        }
+    }
+    // the ANSI left half only lacks the key next to the left shift
+    let left = u8::from(ModuleId::LeftKeyboardHalf);
+    assert!(ANSI.key(left, 20).is_none());
+    assert_eq!(ISO.key(left, 20).unwrap().label, "\\");
+    assert_eq!(ANSI.key(left, 19).unwrap().width, 2.25);
+    assert_eq!(ISO.key(left, 19).unwrap().width, 1.25);
+}
+
+#[test]
+fn hardware_config_selects_the_table() {
+    let right = u8::from(ModuleId::RightKeyboardHalf);
+    let iso = Layout::new(&hardware(true));
+    assert_eq!(iso, ISO);
+    assert_eq!(iso.key(right, 14).unwrap().label, "Enter");
+    assert_eq!(iso.find(right, "#").unwrap().id, 21);
+
+    let ansi = Layout::new(&hardware(false));
+    assert_eq!(ansi, ANSI);
+    assert_eq!(ansi.key(right, 14).unwrap().label, "\\");
+    assert_eq!(ansi.find(right, "enter").unwrap().id, 21);
+    assert!(ansi.find(right, "#").is_none());
+}
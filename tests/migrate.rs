@@ -0,0 +1,114 @@
+use uhkctl::{
+    config::{Backlighting, KeyAction, LayerConfig, ModuleConfig, UserConfig},
+    consts::{MouseActionParam, SwitchLayerMode},
+    device::{ConfigPath, DeviceError, PathSegment, UhkCursor, UhkWriter, Version},
+    migrate::{migrate, Warning},
+};
+
+const USER_CONFIG_V5: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+const USER_CONFIG_V6: &[u8] = include_bytes!("fixtures/user-config-v6.bin");
+
+fn parse(data: &[u8]) -> UserConfig {
+    UserConfig::deserialize(&mut UhkCursor::new(data.to_vec())).unwrap()
+}
+
+fn serialize(config: &UserConfig) -> Vec<u8> {
+    let mut writer = UhkWriter::new();
+    config.serialize(&mut writer).unwrap();
+    writer.into_inner()
+}
+
+/// A layer with one module whose keys are `key_actions`.
+fn layer(id: u8, key_actions: Vec<KeyAction>) -> LayerConfig {
+    LayerConfig {
+        id,
+        modules: vec![ModuleConfig { id: 0, key_actions }],
+    }
+}
+
+#[test]
+fn downgrade_drops_backlighting_and_axis_locks() {
+    let mut config = parse(USER_CONFIG_V6);
+    let warnings = migrate(&mut config, Version::new(5, 0, 0)).unwrap();
+    assert_eq!(
+        warnings,
+        [Warning::Backlighting, Warning::AxisLock { module: 3 }]
+    );
+    assert_eq!(config.backlighting, Backlighting::default());
+    assert_eq!(serialize(&config), USER_CONFIG_V5);
+}
+
+#[test]
+fn downgrade_drops_layers() {
+    let mut config = parse(USER_CONFIG_V6);
+    let layers = &mut config.keymaps[1].layers;
+    let count = layers.len();
+    // fn2 has a key assigned, fn3 none
+    layers.push(layer(
+        4,
+        vec![
+            KeyAction::None,
+            KeyAction::MouseAction(MouseActionParam::LeftClick),
+        ],
+    ));
+    layers.push(layer(5, vec![KeyAction::None]));
+    let warnings = migrate(&mut config, Version::new(5, 0, 0)).unwrap();
+    let path = ConfigPath(vec![PathSegment::Keymap(1), PathSegment::Layer(count)]);
+    assert_eq!(warnings[2..], [Warning::Layer { path, layer: 4 }]);
+    assert_eq!(config.keymaps[1].layers.len(), count);
+    assert_eq!(
+        warnings[2].to_string(),
+        format!("keymap 1/layer {}: fn2 layer dropped", count)
+    );
+}
+
+#[test]
+fn downgrade_unassigns_switches_to_dropped_layers() {
+    let mut config = parse(USER_CONFIG_V6);
+    let keys = &mut config.keymaps[0].layers[0].modules[1].key_actions;
+    keys[2] = KeyAction::SwitchLayer(4, SwitchLayerMode::HoldAndDoubleTapToggle);
+    keys[3] = KeyAction::SwitchLayer(3, SwitchLayerMode::HoldAndDoubleTapToggle);
+    let warnings = migrate(&mut config, Version::new(5, 0, 0)).unwrap();
+    assert_eq!(
+        warnings[2..],
+        [Warning::SwitchLayer {
+            path: ConfigPath(vec![
+                PathSegment::Keymap(0),
+                PathSegment::Layer(0),
+                PathSegment::Module(1),
+                PathSegment::Key(2),
+            ]),
+            layer: 4,
+        }]
+    );
+    let keys = &config.keymaps[0].layers[0].modules[1].key_actions;
+    assert!(matches!(keys[2], KeyAction::None));
+    assert!(matches!(
+        keys[3],
+        KeyAction::SwitchLayer(3, SwitchLayerMode::HoldAndDoubleTapToggle)
+    ));
+}
+
+#[test]
+fn upgrade_parses_back_at_the_target_version() {
+    let mut config = parse(USER_CONFIG_V5);
+    assert_eq!(migrate(&mut config, Version::new(6, 0, 0)).unwrap(), []);
+    let data = serialize(&config);
+    let upgraded = parse(&data);
+    assert_eq!(upgraded.version(), Version::new(6, 0, 0));
+    assert_eq!(upgraded.length as usize, data.len());
+    assert_eq!(upgraded.backlighting, Backlighting::default());
+    assert_eq!(serialize(&upgraded), data);
+}
+
+#[test]
+fn unknown_targets_are_rejected() {
+    for to in [Version::new(7, 0, 0), Version::new(6, 9, 0)] {
+        let mut config = parse(USER_CONFIG_V5);
+        assert!(matches!(
+            migrate(&mut config, to),
+            Err(DeviceError::UnsupportedDataModel(version)) if version == to
+        ));
+        assert_eq!(config.version(), Version::new(5, 0, 0));
+    }
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+use uhkctl::{
+    capture::{Direction, Record},
+    pcapng::{write_pcapng, LINKTYPE_USB_LINUX_MMAPPED},
+};
+
+fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Splits a pcapng file into (block type, block body) pairs, checking that
+/// both length fields agree and blocks are 32 bit aligned.
+fn blocks(mut file: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut blocks = vec![];
+    while !file.is_empty() {
+        let kind = u32_at(file, 0);
+        let length = u32_at(file, 4) as usize;
+        assert_eq!(length % 4, 0);
+        assert_eq!(u32_at(file, length - 4) as usize, length);
+        blocks.push((kind, &file[8..length - 4]));
+        file = &file[length..];
+    }
+    blocks
+}
+
+fn records() -> Vec<Record> {
+    vec![
+        Record {
+            timestamp: Duration::from_micros(1_500_000),
+            direction: Direction::Out,
+            data: vec![0x12, 0x03],
+        },
+        Record {
+            timestamp: Duration::from_micros(1_500_250),
+            direction: Direction::In,
+            data: vec![0x00],
+        },
+    ]
+}
+
+#[test]
+fn block_structure() {
+    let mut file = vec![];
+    write_pcapng(&records(), &mut file).unwrap();
+    let blocks = blocks(&file);
+    let kinds: Vec<_> = blocks.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [0x0a0d0d0a, 1, 6, 6]);
+
+    let (_, shb) = blocks[0];
+    assert_eq!(
+        shb,
+        [
+            0x4d, 0x3c, 0x2b, 0x1a, // byte order magic
+            1, 0, 0, 0, // version 1.0
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // unknown section length
+            4, 0, 6, 0, b'u', b'h', b'k', b'c', b't', b'l', 0, 0, // shb_userappl
+            0, 0, 0, 0, // opt_endofopt
+        ]
+    );
+    assert_eq!(file.len(), 8 + shb.len() + 4 + blocks_len(&blocks[1..]));
+
+    let (_, idb) = blocks[1];
+    assert_eq!(u16_at(idb, 0), LINKTYPE_USB_LINUX_MMAPPED);
+    assert_eq!(idb.len(), 8);
+}
+
+fn blocks_len(blocks: &[(u32, &[u8])]) -> usize {
+    blocks.iter().map(|(_, body)| body.len() + 12).sum()
+}
+
+#[test]
+fn enhanced_packet_blocks() {
+    let mut file = vec![];
+    write_pcapng(&records(), &mut file).unwrap();
+    let blocks = blocks(&file);
+
+    let (_, out) = blocks[2];
+    assert_eq!(u32_at(out, 0), 0);
+    let micros = (u32_at(out, 4) as u64) << 32 | u32_at(out, 8) as u64;
+    assert_eq!(micros, 1_500_000);
+    // captured and original length: usbmon header plus payload
+    assert_eq!(u32_at(out, 12), 66);
+    assert_eq!(u32_at(out, 16), 66);
+    let packet = &out[20..20 + 66];
+    #[rustfmt::skip]
+    let header: [u8; 64] = [
+        1, 0, 0, 0, 0, 0, 0, 0, // urb id
+        b'S', 1, 0x01, 1, // submission, interrupt, endpoint 1 out, device 1
+        1, 0, b'-', 0, // bus 1, no setup packet, data present
+        1, 0, 0, 0, 0, 0, 0, 0, // seconds
+        0x20, 0xa1, 0x07, 0, // 500000 microseconds
+        0x8d, 0xff, 0xff, 0xff, // -EINPROGRESS
+        2, 0, 0, 0, 2, 0, 0, 0, // urb and data length
+        0, 0, 0, 0, 0, 0, 0, 0, // setup
+        1, 0, 0, 0, 0, 0, 0, 0, // interval, start frame
+        0, 0, 0, 0, 0, 0, 0, 0, // transfer flags, iso descriptors
+    ];
+    assert_eq!(packet[..64], header);
+    assert_eq!(packet[64..], [0x12, 0x03]);
+    // packet data padded to 32 bits, then the comment option
+    assert_eq!(out[86..88], [0, 0]);
+    assert_eq!(u16_at(out, 88), 1);
+    assert_eq!(u16_at(out, 90), 11);
+    assert_eq!(&out[92..103], b"GetVariable");
+    assert_eq!(out[103..], [0, 0, 0, 0, 0]);
+
+    let (_, response) = blocks[3];
+    assert_eq!(u32_at(response, 12), 65);
+    let packet = &response[20..20 + 65];
+    assert_eq!(packet[..8], 1u64.to_le_bytes());
+    assert_eq!(packet[8..11], [b'C', 1, 0x81]);
+    assert_eq!(packet[28..32], 0i32.to_le_bytes());
+    assert_eq!(packet[64], 0);
+    assert_eq!(response[85..88], [0, 0, 0]);
+    let comment = b"GetVariable response";
+    assert_eq!(u16_at(response, 90) as usize, comment.len());
+    assert_eq!(&response[92..92 + comment.len()], comment);
+}
+
+#[test]
+#[cfg(feature = "text")]
+fn convert_capture_command() {
+    let dir = std::env::temp_dir().join(format!("uhkctl-pcapng-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let capture = dir.join("session.txt");
+    let lines: Vec<_> = records().iter().map(Record::to_line).collect();
+    std::fs::write(
+        &capture,
+        format!("# uhkctl capture v1\n{}\n", lines.join("\n")),
+    )
+    .unwrap();
+    let output = dir.join("session.pcapng");
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_uhkctl"))
+        .arg("convert-capture")
+        .args([&capture, &output])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let file = std::fs::read(&output).unwrap();
+    let blocks = blocks(&file);
+    let kinds: Vec<_> = blocks.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [0x0a0d0d0a, 1, 6, 6]);
+    assert_eq!(u16_at(blocks[1].1, 0), 220);
+    let mut expected = vec![];
+    write_pcapng(&records(), &mut expected).unwrap();
+    assert_eq!(file, expected);
+    std::fs::remove_dir_all(dir).unwrap();
+}
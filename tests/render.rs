@@ -0,0 +1,127 @@
+use uhkctl::{
+    config::{KeyAction, Keystroke, LayerConfig, Modifiers, ModuleConfig, Scancode, UserConfig},
+    consts::{KeystrokeType, ModuleId, MouseActionParam, SecondaryRole, SwitchLayerMode},
+    device::UhkCursor,
+    layout::Layout,
+    render::{key_label, render_layer, Style},
+};
+
+const USER_CONFIG_V5: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+
+fn config() -> UserConfig {
+    UserConfig::deserialize(&mut UhkCursor::new(USER_CONFIG_V5.to_vec())).unwrap()
+}
+
+fn keystroke(scancode: Option<u16>, modifiers: Option<Modifiers>) -> KeyAction {
+    KeyAction::Keystroke(Keystroke {
+        atype: KeystrokeType::Basic,
+        scancode: scancode.map(Scancode),
+        modifiers,
+        secondary_role: None,
+    })
+}
+
+fn label(tap: &str, hold: Option<&str>) -> (String, Option<String>) {
+    (tap.to_string(), hold.map(str::to_string))
+}
+
+#[test]
+fn key_labels() {
+    let config = config();
+    let cases = [
+        (KeyAction::None, label("", None)),
+        (keystroke(Some(0x06), None), label("C", None)),
+        (
+            keystroke(Some(0x06), Some(Modifiers::LCTRL | Modifiers::LSHIFT)),
+            label("CS-C", None),
+        ),
+        (
+            keystroke(Some(0x06), Some(Modifiers::RALT | Modifiers::RGUI)),
+            label("RARG-C", None),
+        ),
+        (keystroke(None, Some(Modifiers::LALT)), label("LAlt", None)),
+        (keystroke(Some(0xa5), None), label("0xa5", None)),
+        (
+            KeyAction::Keystroke(Keystroke {
+                atype: KeystrokeType::Basic,
+                scancode: Some(Scancode(0x2c)),
+                modifiers: Some(Modifiers::empty()),
+                secondary_role: Some(SecondaryRole::Fn),
+            }),
+            label("SPACE", Some("Fn")),
+        ),
+        (
+            KeyAction::SwitchLayer(1, SwitchLayerMode::HoldAndDoubleTapToggle),
+            label("Mod", None),
+        ),
+        (
+            KeyAction::SwitchLayer(3, SwitchLayerMode::Toggle),
+            label("Mouse", Some("lock")),
+        ),
+        (
+            KeyAction::SwitchLayer(20, SwitchLayerMode::Hold),
+            label("Layer 20", Some("hold")),
+        ),
+        (KeyAction::SwitchKeymap(1), label(">DVO", None)),
+        (KeyAction::SwitchKeymap(9), label(">#9", None)),
+        (
+            KeyAction::MouseAction(MouseActionParam::ScrollUp),
+            label("Wh Up", None),
+        ),
+        (
+            KeyAction::PlayMacroAction(0),
+            label("M:Select all and copy", None),
+        ),
+        (KeyAction::PlayMacroAction(9), label("M:#9", None)),
+    ];
+    for (action, expected) in cases {
+        assert_eq!(key_label(&config, &action), expected, "{}", action);
+    }
+}
+
+/// Draws a layer assigning only the two trackball buttons, returning the
+/// lines of the trackball after those of the halves.
+fn trackball(actions: Vec<KeyAction>, style: Style) -> Vec<String> {
+    let layer = LayerConfig {
+        id: 0,
+        modules: vec![ModuleConfig {
+            id: ModuleId::TrackballRight.into(),
+            key_actions: actions,
+        }],
+    };
+    let out = render_layer(&config(), &layer, &Layout { iso: false }, style);
+    let lines: Vec<_> = out.lines().map(str::to_string).collect();
+    lines[lines.len() - 4..].to_vec()
+}
+
+#[test]
+fn neighbouring_keys_share_borders() {
+    let actions = || vec![keystroke(Some(0x04), None), keystroke(Some(0x05), None)];
+    assert_eq!(
+        trackball(actions(), Style::Unicode),
+        [
+            "┌─────┬─────┐",
+            "│  A  │  B  │",
+            "│     │     │",
+            "└─────┴─────┘",
+        ]
+    );
+    assert_eq!(
+        trackball(actions(), Style::Ascii),
+        [
+            "+-----+-----+",
+            "|  A  |  B  |",
+            "|     |     |",
+            "+-----+-----+",
+        ]
+    );
+}
+
+#[test]
+fn long_legends_are_cut() {
+    let actions = vec![
+        KeyAction::MouseAction(MouseActionParam::LeftClick),
+        KeyAction::PlayMacroAction(0),
+    ];
+    assert_eq!(trackball(actions, Style::Ascii)[1], "|LClic|M:Sel|");
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+use uhkctl::{
+    config::{Keystroke, Scancode},
+    consts::KeystrokeType,
+    scancodes,
+};
+
+const TYPES: [KeystrokeType; 4] = [
+    KeystrokeType::Basic,
+    KeystrokeType::ShortMedia,
+    KeystrokeType::LongMedia,
+    KeystrokeType::System,
+];
+
+#[test]
+fn names_round_trip() {
+    for atype in TYPES {
+        for (code, name) in scancodes::table(atype) {
+            assert_eq!(scancodes::name(atype, *code), Some(*name));
+            let expected = match atype {
+                // consumer usages resolve to the shortest keystroke holding them
+                KeystrokeType::ShortMedia | KeystrokeType::LongMedia if *code > 0xff => {
+                    KeystrokeType::LongMedia
+                }
+                KeystrokeType::LongMedia => KeystrokeType::ShortMedia,
+                _ => atype,
+            };
+            assert_eq!(scancodes::lookup(name), Some((expected, *code)), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn names_are_unique() {
+    let mut all = HashSet::new();
+    for atype in [
+        KeystrokeType::Basic,
+        KeystrokeType::ShortMedia,
+        KeystrokeType::System,
+    ] {
+        let table = scancodes::table(atype);
+        let names: HashSet<_> = table.iter().map(|(_, name)| *name).collect();
+        assert_eq!(names.len(), table.len(), "{:?}", atype);
+        let codes: HashSet<_> = table.iter().map(|(code, _)| *code).collect();
+        assert_eq!(codes.len(), table.len(), "{:?}", atype);
+        // lookup does not know which table a name came from
+        assert!(names.iter().all(|name| all.insert(*name)));
+    }
+}
+
+#[test]
+fn unknown_codes_print_as_hex() {
+    assert_eq!(scancodes::name(KeystrokeType::Basic, 0xa5), None);
+    assert_eq!(scancodes::lookup("KEY_NOPE"), None);
+    let keystroke = Keystroke {
+        atype: KeystrokeType::Basic,
+        scancode: Some(Scancode(0xa5)),
+        modifiers: None,
+        secondary_role: None,
+    };
+    assert_eq!(keystroke.to_string(), "0xa5");
+    let keystroke = Keystroke {
+        atype: KeystrokeType::System,
+        scancode: Some(Scancode(0x04)),
+        ..keystroke
+    };
+    assert_eq!(keystroke.to_string(), "0x04");
+}
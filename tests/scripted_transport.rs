@@ -0,0 +1,39 @@
+use uhkctl::{
+    consts::UsbCommand,
+    device::{Device, DeviceError},
+    transport::ScriptedTransport,
+};
+
+fn switch_keymap(abbr: &str) -> Vec<u8> {
+    let mut report = vec![UsbCommand::SwitchKeymap.into(), abbr.len() as u8];
+    report.extend_from_slice(abbr.as_bytes());
+    report
+}
+
+#[test]
+fn command_round_trip() {
+    let transport = ScriptedTransport::new().expect(&switch_keymap("QWR"), &[0]);
+    let device = Device::open(&transport);
+    device.switch_keymap("QWR").unwrap();
+    assert!(transport.is_done());
+}
+
+#[test]
+fn command_reports_status() {
+    let transport = ScriptedTransport::new().expect(&switch_keymap("DVO"), &[1]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.switch_keymap("DVO"),
+        Err(DeviceError::Status(UsbCommand::SwitchKeymap, 1))
+    ));
+}
+
+#[test]
+fn unexpected_report_is_an_error() {
+    let transport = ScriptedTransport::new().expect(&switch_keymap("QWR"), &[0]);
+    let device = Device::open(&transport);
+    assert!(matches!(
+        device.switch_keymap("COL"),
+        Err(DeviceError::UnexpectedReport(report)) if report == switch_keymap("COL")
+    ));
+}
@@ -0,0 +1,106 @@
+use uhkctl::{
+    config::{KeyAction, LayerConfig, ModuleConfig, UserConfig},
+    consts::{ModuleId, MouseActionParam},
+    device::UhkCursor,
+    layout::Layout,
+    svg,
+};
+
+const USER_CONFIG_V5: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+
+fn config() -> UserConfig {
+    UserConfig::deserialize(&mut UhkCursor::new(USER_CONFIG_V5.to_vec())).unwrap()
+}
+
+fn module(id: ModuleId, key_actions: Vec<KeyAction>) -> ModuleConfig {
+    ModuleConfig {
+        id: id.into(),
+        key_actions,
+    }
+}
+
+fn key_rects<'a>(doc: &'a roxmltree::Document) -> Vec<roxmltree::Node<'a, 'a>> {
+    doc.descendants()
+        .filter(|node| {
+            node.has_tag_name("rect")
+                && node
+                    .attribute("class")
+                    .is_some_and(|class| class.starts_with("key"))
+        })
+        .collect()
+}
+
+#[test]
+fn fixture_keymaps_are_well_formed() {
+    let config = config();
+    for keymap in &config.keymaps {
+        let layout = Layout { iso: false };
+        let svg = svg::render_keymap(&config, keymap, &layout);
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        assert!(doc.root_element().has_tag_name("svg"));
+        let halves = layout.keys(ModuleId::LeftKeyboardHalf.into()).len()
+            + layout.keys(ModuleId::RightKeyboardHalf.into()).len();
+        assert!(key_rects(&doc).len() >= halves * keymap.layers.len());
+    }
+}
+
+#[test]
+fn names_are_escaped() {
+    let mut config = config();
+    config.keymaps[0].name = "Tom & Jerry <3".to_string();
+    let svg = svg::render_keymap(&config, &config.keymaps[0], &Layout { iso: false });
+    assert!(svg.contains("Tom &amp; Jerry &lt;3 (QWR) / base"));
+    let doc = roxmltree::Document::parse(&svg).unwrap();
+    let title = doc
+        .descendants()
+        .find(|node| node.attribute("class") == Some("title"))
+        .unwrap();
+    assert_eq!(title.text(), Some("Tom & Jerry <3 (QWR) / base"));
+}
+
+#[test]
+fn only_modules_with_a_layout_get_panels() {
+    let mut config = config();
+    let click = || KeyAction::MouseAction(MouseActionParam::LeftClick);
+    config.keymaps[0].layers = vec![LayerConfig {
+        id: 0,
+        modules: vec![
+            module(ModuleId::KeyClusterLeft, vec![click(), click(), click()]),
+            module(ModuleId::TouchpadRight, vec![click()]),
+        ],
+    }];
+    let layout = Layout { iso: true };
+    let svg = svg::render_keymap(&config, &config.keymaps[0], &layout);
+    let doc = roxmltree::Document::parse(&svg).unwrap();
+    let rects = key_rects(&doc);
+    assert_eq!(rects.len(), 32 + 34 + 3);
+    // the cluster keys are the only ones assigned
+    let assigned: Vec<_> = rects
+        .iter()
+        .filter(|rect| rect.attribute("class") == Some("key"))
+        .collect();
+    assert_eq!(assigned.len(), 3);
+}
+
+#[test]
+fn long_legends_are_squeezed() {
+    let mut config = config();
+    config.keymaps[0].layers = vec![LayerConfig {
+        id: 0,
+        modules: vec![module(
+            ModuleId::TrackballRight,
+            vec![KeyAction::PlayMacroAction(0), KeyAction::SwitchKeymap(1)],
+        )],
+    }];
+    let svg = svg::render_keymap(&config, &config.keymaps[0], &Layout { iso: false });
+    let doc = roxmltree::Document::parse(&svg).unwrap();
+    let legend = |text: &str| {
+        doc.descendants()
+            .find(|node| node.attribute("class") == Some("tap") && node.text() == Some(text))
+            .unwrap()
+    };
+    let long = legend("M:Select all and copy");
+    assert_eq!(long.attribute("textLength"), Some("42"));
+    assert_eq!(long.attribute("lengthAdjust"), Some("spacingAndGlyphs"));
+    assert_eq!(legend(">DVO").attribute("textLength"), None);
+}
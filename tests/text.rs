@@ -0,0 +1,372 @@
+#![cfg(feature = "text")]
+use std::{fs, path::PathBuf};
+use uhkctl::{
+    config::{KeyAction, Keystroke, Modifiers, Scancode, UserConfig},
+    consts::{KeystrokeType, MouseActionParam},
+    device::{ConfigPath, PathSegment, UhkCursor, UhkWriter},
+    text::{self, TextError},
+};
+
+const USER_CONFIG_V5: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+
+const HEADER: &str = r#"
+name = "Test"
+version = { major = 5, minor = 0, patch = 0 }
+double_tap_switch_layer_timeout = 250
+icons_and_layer_texts_brightness = 255
+alphanumeric_segments_brightness = 255
+key_backlight_brightness = 255
+
+[mouse]
+move_initial_speed = 5
+move_acceleration = 35
+move_decelerated_speed = 10
+move_base_speed = 40
+move_accelerated_speed = 80
+scroll_initial_speed = 20
+scroll_acceleration = 20
+scroll_decelerated_speed = 10
+scroll_base_speed = 20
+scroll_accelerated_speed = 50
+"#;
+
+/// Compiles a config with one keymap holding one layer with the given
+/// module grids, `prelude` going in between the settings and the keymap.
+fn compile_with(prelude: &str, grids: &str) -> Result<UserConfig, TextError> {
+    text::compile(&format!(
+        "{}\n{}\n[[keymaps]]\nabbr = \"QWR\"\nname = \"QWERTY\"\n\n[[keymaps.layers]]\nlayer = \"base\"\n{}",
+        HEADER, prelude, grids
+    ))
+}
+
+fn compile_layer(grids: &str) -> Result<UserConfig, TextError> {
+    compile_with("", grids)
+}
+
+fn parse(data: &[u8]) -> UserConfig {
+    UserConfig::deserialize(&mut UhkCursor::new(data.to_vec())).unwrap()
+}
+
+fn serialize(config: &UserConfig) -> Vec<u8> {
+    let mut writer = UhkWriter::new();
+    config.serialize(&mut writer).unwrap();
+    writer.into_inner()
+}
+
+/// Writes `files` to a fresh directory for one test.
+fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("uhkctl-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (name, contents) in files {
+        fs::write(dir.join(name), contents).unwrap();
+    }
+    dir
+}
+
+fn key_actions(config: &UserConfig, module: u8) -> &[KeyAction] {
+    let layer = &config.keymaps[0].layers[0];
+    let module = layer.modules.iter().find(|m| m.id == module).unwrap();
+    &module.key_actions
+}
+
+fn keystroke(scancode: u16, modifiers: Option<Modifiers>) -> Keystroke {
+    Keystroke {
+        atype: KeystrokeType::Basic,
+        scancode: Some(Scancode(scancode)),
+        modifiers,
+        secondary_role: None,
+    }
+}
+
+#[test]
+fn small_layer() {
+    let config = compile_layer(
+        r#"
+key-cluster = "mouse:leftClick _ LCtrl+KEY_C"
+left = """
+KEY_GRAVE KEY_1
+"""
+"#,
+    )
+    .unwrap();
+    let layer = &config.keymaps[0].layers[0];
+    assert_eq!(layer.id, 0);
+    let ids: Vec<_> = layer.modules.iter().map(|module| module.id).collect();
+    assert_eq!(ids, [1, 2]);
+
+    let left = &layer.modules[0].key_actions;
+    assert_eq!(left.len(), 2);
+    assert!(matches!(&left[0], KeyAction::Keystroke(key) if *key == keystroke(0x35, None)));
+    assert!(matches!(&left[1], KeyAction::Keystroke(key) if *key == keystroke(0x1e, None)));
+
+    let cluster = &layer.modules[1].key_actions;
+    assert!(matches!(
+        cluster[0],
+        KeyAction::MouseAction(MouseActionParam::LeftClick)
+    ));
+    assert!(matches!(cluster[1], KeyAction::None));
+    assert!(matches!(
+        &cluster[2],
+        KeyAction::Keystroke(key) if *key == keystroke(0x06, Some(Modifiers::LCTRL))
+    ));
+}
+
+#[test]
+fn unknown_tokens_are_reported() {
+    let err = compile_layer(r#"left = "KEY_GRAVE KEY_NOPE""#).unwrap_err();
+    assert!(matches!(
+        &err,
+        TextError::Invalid { what: "key action", token, path }
+            if token == "KEY_NOPE"
+                && *path
+                    == ConfigPath(vec![
+                        PathSegment::Keymap(0),
+                        PathSegment::Layer(0),
+                        PathSegment::Module(1),
+                        PathSegment::Key(1),
+                    ])
+    ));
+    assert_eq!(
+        err.to_string(),
+        r#"keymap 0/layer 0/module 1/key 1: invalid key action "KEY_NOPE""#
+    );
+}
+
+#[test]
+fn short_rows_are_reported() {
+    let err = compile_layer(
+        r#"
+left = """
+KEY_GRAVE KEY_1 KEY_2 KEY_3 KEY_4 KEY_5
+KEY_TAB
+"""
+"#,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        TextError::GridRow {
+            row: 0,
+            keys: 6,
+            expected: 7,
+            ..
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "keymap 0/layer 0/module 1: grid row 0 has 6 keys, the module has 7"
+    );
+}
+
+#[test]
+fn long_rows_are_reported() {
+    let err = compile_layer(r#"key-cluster = "_ _ _ _""#).unwrap_err();
+    assert!(matches!(
+        err,
+        TextError::GridRow {
+            row: 0,
+            keys: 4,
+            expected: 3,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn decompile_round_trip() {
+    let config = parse(USER_CONFIG_V5);
+    let text = text::decompile(&config).unwrap();
+    assert_eq!(serialize(&text::compile(&text).unwrap()), USER_CONFIG_V5);
+}
+
+#[test]
+fn keystrokes_without_keys_round_trip() {
+    let mut config = parse(USER_CONFIG_V5);
+    let keystrokes = [
+        (KeystrokeType::Basic, None, Some(Modifiers::empty())),
+        (KeystrokeType::ShortMedia, None, None),
+        (KeystrokeType::System, None, Some(Modifiers::LALT)),
+        (
+            KeystrokeType::Basic,
+            Some(Scancode(4)),
+            Some(Modifiers::empty()),
+        ),
+        (KeystrokeType::Basic, None, None),
+    ];
+    let actions = &mut config.keymaps[0].layers[0].modules[0].key_actions;
+    for (action, (atype, scancode, modifiers)) in actions.iter_mut().zip(keystrokes) {
+        *action = KeyAction::Keystroke(Keystroke {
+            atype,
+            scancode,
+            modifiers,
+            secondary_role: None,
+        });
+    }
+    let data = serialize(&config);
+    let text = text::decompile(&config).unwrap();
+    let tokens: Vec<_> = text.split_whitespace().collect();
+    for token in [
+        "NoMods",
+        "shortMedia:-",
+        "LAlt+system:-",
+        "NoMods+KEY_A",
+        "-",
+    ] {
+        assert!(tokens.contains(&token), "{} missing", token);
+    }
+    assert_eq!(serialize(&text::compile(&text).unwrap()), data);
+}
+
+#[test]
+fn includes_are_merged() {
+    let base = format!(
+        "{}\n[aliases]\ncopy = \"LCtrl+KEY_C\"\n\n[[macros]]\nname = \"A\"\nactions = [\"delay 10\"]\n",
+        HEADER
+    );
+    let main = r#"
+include = ["base.toml"]
+name = "Main"
+
+[[macros]]
+name = "B"
+actions = ["text hi"]
+
+[[keymaps]]
+abbr = "QWR"
+name = "QWERTY"
+
+[[keymaps.layers]]
+layer = "base"
+key-cluster = "$copy macro:A macro:B"
+"#;
+    let dir = write_files("include", &[("base.toml", &base), ("main.toml", main)]);
+    let config = text::compile_file(&dir.join("main.toml")).unwrap();
+    assert_eq!(config.name, "Main");
+    assert_eq!(config.double_tap_switch_layer_timeout, 250);
+    let macros: Vec<_> = config.macros.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(macros, ["A", "B"]);
+    let actions = key_actions(&config, 2);
+    assert!(matches!(
+        &actions[0],
+        KeyAction::Keystroke(key) if *key == keystroke(0x06, Some(Modifiers::LCTRL))
+    ));
+    assert!(matches!(actions[1], KeyAction::PlayMacroAction(0)));
+    assert!(matches!(actions[2], KeyAction::PlayMacroAction(1)));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn shared_includes_are_read_once() {
+    let base = format!(
+        "{}\n[[macros]]\nname = \"Base\"\nactions = [\"delay 10\"]\n",
+        HEADER
+    );
+    let left = "include = [\"base.toml\"]\n\n[[macros]]\nname = \"Left\"\nactions = []\n";
+    let right = "include = [\"base.toml\"]\n\n[[macros]]\nname = \"Right\"\nactions = []\n";
+    let main = r#"
+include = ["left.toml", "right.toml"]
+
+[[keymaps]]
+abbr = "QWR"
+name = "QWERTY"
+
+[[keymaps.layers]]
+layer = "base"
+key-cluster = "macro:Base macro:Left macro:Right"
+"#;
+    let dir = write_files(
+        "include-diamond",
+        &[
+            ("base.toml", &base),
+            ("left.toml", left),
+            ("right.toml", right),
+            ("main.toml", main),
+        ],
+    );
+    let config = text::compile_file(&dir.join("main.toml")).unwrap();
+    let macros: Vec<_> = config.macros.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(macros, ["Base", "Left", "Right"]);
+    assert_eq!(config.keymaps.len(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn include_cycles_are_reported() {
+    let a = format!("include = [\"b.toml\"]\n{}", HEADER);
+    let dir = write_files(
+        "include-cycle",
+        &[("a.toml", &a), ("b.toml", "include = [\"a.toml\"]\n")],
+    );
+    assert!(matches!(
+        text::compile_file(&dir.join("a.toml")),
+        Err(TextError::IncludeCycle(path)) if path.ends_with("a.toml")
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn aliases_are_expanded() {
+    let prelude = "[aliases]\ncopy = \"LCtrl+KEY_C\"\n";
+    let config = compile_with(prelude, r#"key-cluster = "$copy _ $copy""#).unwrap();
+    let actions = key_actions(&config, 2);
+    for action in [&actions[0], &actions[2]] {
+        assert!(matches!(
+            action,
+            KeyAction::Keystroke(key) if *key == keystroke(0x06, Some(Modifiers::LCTRL))
+        ));
+    }
+    let err = compile_with(prelude, r#"key-cluster = "$paste""#).unwrap_err();
+    assert!(matches!(
+        err,
+        TextError::Invalid { what: "alias", token, .. } if token == "$paste"
+    ));
+}
+
+#[test]
+fn layers_inherit_from_what_they_extend() {
+    let prelude = r#"
+[layers.numbers]
+left = "KEY_1 KEY_2 KEY_3"
+
+[layers.shifted]
+extends = "numbers"
+left = ". LShift+KEY_2"
+"#;
+    let config = compile_with(prelude, "extends = \"shifted\"\nleft = \". . KEY_X\"").unwrap();
+    let actions = key_actions(&config, 1);
+    assert_eq!(actions.len(), 3);
+    assert!(matches!(&actions[0], KeyAction::Keystroke(key) if *key == keystroke(0x1e, None)));
+    assert!(matches!(
+        &actions[1],
+        KeyAction::Keystroke(key) if *key == keystroke(0x1f, Some(Modifiers::LSHIFT))
+    ));
+    assert!(matches!(&actions[2], KeyAction::Keystroke(key) if *key == keystroke(0x1b, None)));
+
+    let err = compile_with(prelude, "extends = \"numbers\"\nleft = \". . . .\"").unwrap_err();
+    assert!(matches!(
+        err,
+        TextError::Invalid { what: "inherited key", path, .. }
+            if path == ConfigPath(vec![
+                PathSegment::Keymap(0),
+                PathSegment::Layer(0),
+                PathSegment::Module(1),
+                PathSegment::Key(3),
+            ])
+    ));
+}
+
+#[test]
+fn extends_cycles_are_reported() {
+    let prelude = r#"
+[layers.a]
+extends = "b"
+
+[layers.b]
+extends = "a"
+"#;
+    assert!(matches!(
+        compile_with(prelude, "extends = \"a\""),
+        Err(TextError::ExtendsCycle(layer)) if layer == "a"
+    ));
+}
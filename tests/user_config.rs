@@ -0,0 +1,253 @@
+use uhkctl::{
+    config::{Backlighting, KeyAction, Keystroke, MacroAction, Rgb, Scancode, UserConfig},
+    consts::{BacklightingMode, KeystrokeType, MouseActionParam, SecondaryRole},
+    device::{ConfigPath, DeviceError, ParseError, PathSegment, UhkCursor, UhkWriter, Version},
+};
+
+const USER_CONFIG_V5: &[u8] = include_bytes!("fixtures/user-config-v5.bin");
+/// The v5 fixture in the v6 layout, with uniform backlighting and cursor
+/// axis locking on the trackball.
+const USER_CONFIG_V6: &[u8] = include_bytes!("fixtures/user-config-v6.bin");
+
+fn parse(data: &[u8]) -> UserConfig {
+    UserConfig::deserialize(&mut UhkCursor::new(data.to_vec())).unwrap()
+}
+
+/// Parses `data` with the byte at `offset` replaced by `id`, expecting a
+/// parse error there.
+fn parse_error(data: &[u8], offset: usize, id: u8) -> ParseError {
+    let mut data = data.to_vec();
+    data[offset] = id;
+    match UserConfig::deserialize(&mut UhkCursor::new(data)) {
+        Err(DeviceError::Parse(err)) => {
+            assert_eq!((err.id, err.offset), (id, offset as u64));
+            err
+        }
+        other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+    }
+}
+
+fn serialize(config: &UserConfig) -> Result<Vec<u8>, DeviceError> {
+    let mut writer = UhkWriter::new();
+    config.serialize(&mut writer)?;
+    Ok(writer.into_inner())
+}
+
+#[test]
+fn round_trip_is_byte_identical() {
+    let config = parse(USER_CONFIG_V5);
+    assert_eq!(config.length as usize, USER_CONFIG_V5.len());
+    assert_eq!(serialize(&config).unwrap(), USER_CONFIG_V5);
+}
+
+#[test]
+fn long_strings_use_the_wide_length() {
+    let mut config = parse(USER_CONFIG_V5);
+    config.name = "x".repeat(300);
+    let data = serialize(&config).unwrap();
+    let name = &data[8..];
+    assert_eq!(name[..3], [0xff, 0x2c, 0x01]);
+    assert_eq!(parse(&data).name, config.name);
+}
+
+#[test]
+fn oversized_strings_are_an_error() {
+    let mut config = parse(USER_CONFIG_V5);
+    config.macros[0].actions[0] = MacroAction::Text("x".repeat(0x10000));
+    assert!(matches!(
+        serialize(&config),
+        Err(DeviceError::Overflow {
+            what: "length",
+            value: 0x10000
+        })
+    ));
+}
+
+#[test]
+fn oversized_configs_are_an_error() {
+    let mut config = parse(USER_CONFIG_V5);
+    for action in &mut config.macros[0].actions {
+        *action = MacroAction::Text("x".repeat(0xfff0));
+    }
+    assert!(matches!(
+        serialize(&config),
+        Err(DeviceError::Overflow {
+            what: "config length",
+            ..
+        })
+    ));
+}
+
+// module configurations follow the name "My UHK", the timeout, brightness
+// and mouse settings, and their count
+const MODULE_CONFIGURATIONS: usize = 31;
+
+#[test]
+fn unknown_pointer_mode() {
+    let err = parse_error(USER_CONFIG_V5, MODULE_CONFIGURATIONS + 1, 9);
+    assert_eq!(err.what, "pointer mode");
+    assert_eq!(
+        err.path,
+        ConfigPath(vec![PathSegment::ModuleConfiguration(0)])
+    );
+}
+
+#[test]
+fn unknown_pointer_function() {
+    let err = parse_error(USER_CONFIG_V5, MODULE_CONFIGURATIONS + 10 + 9, 9);
+    assert_eq!(err.what, "pointer function");
+    assert_eq!(
+        err.path,
+        ConfigPath(vec![PathSegment::ModuleConfiguration(1)])
+    );
+    assert_eq!(
+        err.to_string(),
+        "unknown pointer function id 9 at offset 50 in module configuration 1"
+    );
+}
+
+#[test]
+fn unknown_macro_action() {
+    let name = b"Select all and copy";
+    let macro_ = USER_CONFIG_V5
+        .windows(name.len())
+        .position(|window| window == name)
+        .unwrap();
+    let err = parse_error(USER_CONFIG_V5, macro_ + name.len() + 1, 0xfe);
+    assert_eq!(err.what, "macro action");
+    assert_eq!(
+        err.path,
+        ConfigPath(vec![PathSegment::Macro(0), PathSegment::Action(0)])
+    );
+}
+
+#[test]
+fn unknown_secondary_role() {
+    let mut config = parse(USER_CONFIG_V5);
+    config.keymaps[0].layers[0].modules[0].key_actions[0] = KeyAction::Keystroke(Keystroke {
+        atype: KeystrokeType::Basic,
+        scancode: Some(Scancode(4)),
+        modifiers: None,
+        secondary_role: Some(SecondaryRole::LeftCtrl),
+    });
+    let data = serialize(&config).unwrap();
+    // the first key action follows the QWR keymap header and the counts and
+    // ids of its first layer and module
+    let keymap = data
+        .windows(4)
+        .position(|window| window == b"\x03QWR")
+        .unwrap();
+    let action = keymap + 4 + 1 + 7 + 1 + b"The standard layout.".len() + 5;
+    // action id and scancode, then the role
+    let err = parse_error(&data, action + 2, 11);
+    assert_eq!(err.what, "secondary role");
+    assert_eq!(
+        err.path,
+        ConfigPath(vec![
+            PathSegment::Keymap(0),
+            PathSegment::Layer(0),
+            PathSegment::Module(0),
+            PathSegment::Key(0),
+        ])
+    );
+}
+
+#[test]
+fn unknown_mouse_action() {
+    let mut config = parse(USER_CONFIG_V5);
+    config.keymaps[0].layers[0].modules[0].key_actions[0] =
+        KeyAction::MouseAction(MouseActionParam::ScrollDown);
+    let data = serialize(&config).unwrap();
+    let keymap = data
+        .windows(4)
+        .position(|window| window == b"\x03QWR")
+        .unwrap();
+    let action = keymap + 4 + 1 + 7 + 1 + b"The standard layout.".len() + 5;
+    let err = parse_error(&data, action + 1, 0xee);
+    assert_eq!(err.what, "mouse action");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "unknown mouse action id 238 at offset {} in keymap 0/layer 0/module 0/key 0",
+            action + 1
+        )
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn key_actions_serialize_with_names() {
+    use uhkctl::consts::SwitchLayerMode;
+
+    let value = serde_json::to_value([
+        KeyAction::SwitchLayer(1, SwitchLayerMode::Toggle),
+        KeyAction::MouseAction(MouseActionParam::LeftClick),
+    ])
+    .unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!([
+            { "SwitchLayer": [1, "Toggle"] },
+            { "MouseAction": "LeftClick" },
+        ])
+    );
+}
+
+#[test]
+fn v5_layout() {
+    let config = parse(USER_CONFIG_V5);
+    assert_eq!(config.version(), Version::new(5, 0, 0));
+    assert_eq!(config.backlighting, Backlighting::default());
+    assert!(config
+        .module_configurations
+        .iter()
+        .all(|module| !module.cursor_axis_lock && !module.scroll_axis_lock));
+    assert_eq!(config.keymaps.len(), 2);
+    assert_eq!(config.macros.len(), 3);
+}
+
+#[test]
+fn v6_layout() {
+    let mut config = parse(USER_CONFIG_V6);
+    assert_eq!(config.version(), Version::new(6, 0, 0));
+    assert_eq!(config.length as usize, USER_CONFIG_V6.len());
+    assert_eq!(
+        config.backlighting,
+        Backlighting {
+            mode: BacklightingMode::Uniform,
+            color: Rgb {
+                r: 0x12,
+                g: 0x34,
+                b: 0x56
+            },
+        }
+    );
+    let trackball = &config.module_configurations[2];
+    assert_eq!(trackball.id, 3);
+    assert!(trackball.cursor_axis_lock && !trackball.scroll_axis_lock);
+    assert_eq!(serialize(&config).unwrap(), USER_CONFIG_V6);
+
+    // everything else reads into the same model as the v5 layout
+    config.major = 5;
+    config.backlighting = Backlighting::default();
+    config.module_configurations[2].cursor_axis_lock = false;
+    assert_eq!(serialize(&config).unwrap(), USER_CONFIG_V5);
+}
+
+#[test]
+fn unknown_backlighting_mode() {
+    // after the name and the timeout and brightness settings
+    let err = parse_error(USER_CONFIG_V6, 20, 2);
+    assert_eq!(err.what, "backlighting mode");
+    assert_eq!(err.path, ConfigPath::default());
+}
+
+#[test]
+fn unsupported_data_models_are_not_serialized() {
+    let mut config = parse(USER_CONFIG_V5);
+    config.major = 7;
+    assert!(matches!(
+        serialize(&config),
+        Err(DeviceError::UnsupportedDataModel(version)) if version == Version::new(7, 0, 0)
+    ));
+}